@@ -0,0 +1,544 @@
+use cozy_chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_pawn_quiets,
+    get_rook_moves, BitBoard, Color, File, Move, Piece, Rank, Square,
+};
+use std::fmt;
+
+/// The pieces a pawn may promote to. Unlike standard chess a pawn may become a king
+const PROMOTION_PIECES: [Piece; 5] = [
+    Piece::Queen,
+    Piece::Rook,
+    Piece::Bishop,
+    Piece::Knight,
+    Piece::King,
+];
+
+/// The material value of every piece, in antichess each piece is a liability
+const PIECE_VALUE: i32 = 100;
+
+/// Zobrist keys for every piece of every colour on every square,
+/// followed by the side to move and the en passant files
+const ZOBRIST_KEYS: [u64; 2 * 6 * 64 + 1 + 8] = {
+    let mut keys = [0; 2 * 6 * 64 + 1 + 8];
+    let mut state: u64 = 0x5768_6561_746c_6579;
+    let mut index = 0;
+    while index < keys.len() {
+        // splitmix64
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut key = state;
+        key = (key ^ (key >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        key = (key ^ (key >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        keys[index] = key ^ (key >> 31);
+        index += 1;
+    }
+    keys
+};
+const SIDE_TO_MOVE_KEY: usize = 2 * 6 * 64;
+const EN_PASSANT_KEYS: usize = SIDE_TO_MOVE_KEY + 1;
+
+/// The reason a FEN could not be read as an antichess position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FenError(&'static str);
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A position in Antichess, also known as Losing Chess.
+/// The king is an ordinary piece that may be captured, there is no check and no castling,
+/// captures are compulsory and a player wins by losing all their pieces or being stalemated.
+/// cozy-chess boards always have one royal king per side, so antichess positions are
+/// kept separately and only share the move and bitboard types with cozy-chess
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AntichessBoard {
+    pieces: [BitBoard; Piece::NUM],
+    colors: [BitBoard; Color::NUM],
+    side_to_move: Color,
+    /// Only set when the side to move can capture en passant
+    en_passant: Option<File>,
+    halfmove_clock: u8,
+    fullmove_number: u16,
+    hash: u64,
+}
+
+impl AntichessBoard {
+    /// Returns the antichess starting position, which is the standard one without castling
+    pub fn startpos() -> AntichessBoard {
+        AntichessBoard::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1").unwrap()
+    }
+
+    /// Reads a position from a FEN. The castling field is ignored,
+    /// and the clocks may be left out
+    pub fn from_fen(fen: &str) -> Result<AntichessBoard, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 2 {
+            return Err(FenError("The FEN is missing a field."));
+        }
+        if fields.len() > 6 {
+            return Err(FenError("The FEN has too many fields."));
+        }
+        let mut board = AntichessBoard {
+            pieces: [BitBoard::EMPTY; Piece::NUM],
+            colors: [BitBoard::EMPTY; Color::NUM],
+            side_to_move: Color::White,
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            hash: 0,
+        };
+
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != Rank::NUM {
+            return Err(FenError("The board is invalid."));
+        }
+        for (rank_index, rank_text) in ranks.iter().enumerate() {
+            let rank = Rank::index(Rank::NUM - 1 - rank_index);
+            let mut file_index = 0;
+            for symbol in rank_text.chars() {
+                if let Some(empty_squares) = symbol.to_digit(10) {
+                    file_index += empty_squares as usize;
+                    continue;
+                }
+                let piece: Piece = symbol
+                    .to_ascii_lowercase()
+                    .try_into()
+                    .map_err(|_| FenError("The board is invalid."))?;
+                let color = match symbol.is_ascii_uppercase() {
+                    true => Color::White,
+                    false => Color::Black,
+                };
+                let file = File::try_index(file_index).ok_or(FenError("The board is invalid."))?;
+                board.put(piece, color, Square::new(file, rank));
+                file_index += 1;
+            }
+            if file_index != File::NUM {
+                return Err(FenError("The board is invalid."));
+            }
+        }
+        let back_ranks = Rank::First.bitboard() | Rank::Eighth.bitboard();
+        if !(board.pieces(Piece::Pawn) & back_ranks).is_empty() {
+            return Err(FenError("The board is invalid."));
+        }
+
+        board.side_to_move = match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err(FenError("The side to move is invalid.")),
+        };
+        if let Some(en_passant) = fields.get(3).filter(|field| **field != "-") {
+            let square: Square = en_passant
+                .parse()
+                .map_err(|_| FenError("The en passant square is invalid."))?;
+            if square.rank() != Rank::Sixth.relative_to(board.side_to_move) {
+                return Err(FenError("The en passant square is invalid."));
+            }
+            board.set_en_passant(square);
+        }
+        if let Some(halfmove_clock) = fields.get(4) {
+            board.halfmove_clock = halfmove_clock
+                .parse()
+                .map_err(|_| FenError("The halfmove clock is invalid."))?;
+        }
+        if let Some(fullmove_number) = fields.get(5) {
+            board.fullmove_number = fullmove_number
+                .parse()
+                .map_err(|_| FenError("The fullmove number is invalid."))?;
+        }
+        board.hash = board.calculate_hash();
+        Ok(board)
+    }
+
+    /// Returns the squares of every piece of this type, regardless of colour
+    pub fn pieces(&self, piece: Piece) -> BitBoard {
+        self.pieces[piece as usize]
+    }
+
+    /// Returns the squares of every piece of this colour
+    pub fn colors(&self, color: Color) -> BitBoard {
+        self.colors[color as usize]
+    }
+
+    /// Returns the squares of every piece of this type and colour
+    pub fn colored_pieces(&self, color: Color, piece: Piece) -> BitBoard {
+        self.pieces(piece) & self.colors(color)
+    }
+
+    /// Returns the squares of every piece on the board
+    pub fn occupied(&self) -> BitBoard {
+        self.colors(Color::White) | self.colors(Color::Black)
+    }
+
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    pub fn halfmove_clock(&self) -> u8 {
+        self.halfmove_clock
+    }
+
+    pub fn fullmove_number(&self) -> u16 {
+        self.fullmove_number
+    }
+
+    /// Returns the zobrist hash of the position
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Returns the piece on the square, if any
+    pub fn piece_on(&self, square: Square) -> Option<Piece> {
+        Piece::ALL
+            .into_iter()
+            .find(|piece| self.pieces(*piece).has(square))
+    }
+
+    /// Returns the colour of the piece on the square, if any
+    pub fn color_on(&self, square: Square) -> Option<Color> {
+        Color::ALL
+            .into_iter()
+            .find(|color| self.colors(*color).has(square))
+    }
+
+    /// Returns every legal move. Captures are compulsory, so if any
+    /// capture is possible only the captures are returned
+    pub fn generate_moves(&self) -> Vec<Move> {
+        let side_to_move = self.side_to_move;
+        let ours = self.colors(side_to_move);
+        let theirs = self.colors(!side_to_move);
+        let occupied = self.occupied();
+        let en_passant = self.en_passant_square();
+        let mut captures = Vec::new();
+        let mut quiets = Vec::new();
+        for from in ours {
+            let piece = self.piece_on(from).unwrap();
+            let (capture_targets, quiet_targets) = match piece {
+                Piece::Pawn => {
+                    let attacks = get_pawn_attacks(from, side_to_move);
+                    let mut capture_targets = attacks & theirs;
+                    if let Some(en_passant) = en_passant.filter(|square| attacks.has(*square)) {
+                        capture_targets |= en_passant.bitboard();
+                    }
+                    (
+                        capture_targets,
+                        get_pawn_quiets(from, side_to_move, occupied),
+                    )
+                }
+                _ => {
+                    let targets = match piece {
+                        Piece::Knight => get_knight_moves(from),
+                        Piece::Bishop => get_bishop_moves(from, occupied),
+                        Piece::Rook => get_rook_moves(from, occupied),
+                        Piece::Queen => {
+                            get_bishop_moves(from, occupied) | get_rook_moves(from, occupied)
+                        }
+                        _ => get_king_moves(from),
+                    };
+                    (targets & theirs, targets & !occupied)
+                }
+            };
+            let promotes = piece == Piece::Pawn;
+            for to in capture_targets {
+                push_moves(&mut captures, from, to, promotes);
+            }
+            if captures.is_empty() {
+                for to in quiet_targets {
+                    push_moves(&mut quiets, from, to, promotes);
+                }
+            }
+        }
+        if captures.is_empty() {
+            quiets
+        } else {
+            captures
+        }
+    }
+
+    /// Returns true if the move may be played in this position
+    pub fn is_legal(&self, played_move: Move) -> bool {
+        self.generate_moves().contains(&played_move)
+    }
+
+    /// Plays a move, which must be legal
+    pub fn play(&mut self, played_move: Move) {
+        let side_to_move = self.side_to_move;
+        let piece = self
+            .piece_on(played_move.from)
+            .expect("No piece to move was found.");
+        let captured = self.piece_on(played_move.to);
+        let en_passant_capture =
+            piece == Piece::Pawn && Some(played_move.to) == self.en_passant_square();
+
+        self.remove(piece, side_to_move, played_move.from);
+        if let Some(captured) = captured {
+            self.remove(captured, !side_to_move, played_move.to);
+        }
+        if en_passant_capture {
+            let captured_pawn = Square::new(played_move.to.file(), played_move.from.rank());
+            self.remove(Piece::Pawn, !side_to_move, captured_pawn);
+        }
+        self.put(
+            played_move.promotion.unwrap_or(piece),
+            side_to_move,
+            played_move.to,
+        );
+
+        if piece == Piece::Pawn || captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1);
+        }
+        if side_to_move == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.side_to_move = !side_to_move;
+        self.en_passant = None;
+        let double_push = piece == Piece::Pawn
+            && played_move.from.rank() == Rank::Second.relative_to(side_to_move)
+            && played_move.to.rank() == Rank::Fourth.relative_to(side_to_move);
+        if double_push {
+            let skipped = Square::new(
+                played_move.from.file(),
+                Rank::Third.relative_to(side_to_move),
+            );
+            self.set_en_passant(skipped);
+        }
+        self.hash = self.calculate_hash();
+    }
+
+    /// Returns the winner if the game is over. The side to move wins
+    /// when it has no pieces left or no moves to play
+    pub fn winner(&self) -> Option<Color> {
+        if self.colors(self.side_to_move).is_empty() || self.generate_moves().is_empty() {
+            Some(self.side_to_move)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the evaluation for white in centipawns. Every piece
+    /// is worth the same and having fewer of them is better
+    pub fn evaluate(&self) -> i32 {
+        let white_pieces = self.colors(Color::White).len() as i32;
+        let black_pieces = self.colors(Color::Black).len() as i32;
+        (black_pieces - white_pieces) * PIECE_VALUE
+    }
+
+    fn en_passant_square(&self) -> Option<Square> {
+        self.en_passant
+            .map(|file| Square::new(file, Rank::Sixth.relative_to(self.side_to_move)))
+    }
+
+    /// Records the en passant square if a pawn of the side to move can capture on it
+    fn set_en_passant(&mut self, square: Square) {
+        let side_to_move = self.side_to_move;
+        let capturers = get_pawn_attacks(square, !side_to_move)
+            & self.colored_pieces(side_to_move, Piece::Pawn);
+        if !capturers.is_empty() {
+            self.en_passant = Some(square.file());
+        }
+    }
+
+    fn put(&mut self, piece: Piece, color: Color, square: Square) {
+        self.pieces[piece as usize] |= square.bitboard();
+        self.colors[color as usize] |= square.bitboard();
+    }
+
+    fn remove(&mut self, piece: Piece, color: Color, square: Square) {
+        self.pieces[piece as usize] &= !square.bitboard();
+        self.colors[color as usize] &= !square.bitboard();
+    }
+
+    fn calculate_hash(&self) -> u64 {
+        let mut hash = 0;
+        for square in self.occupied() {
+            let piece = self.piece_on(square).unwrap() as usize;
+            let color = self.color_on(square).unwrap() as usize;
+            hash ^= ZOBRIST_KEYS[(color * Piece::NUM + piece) * Square::NUM + square as usize];
+        }
+        if self.side_to_move == Color::Black {
+            hash ^= ZOBRIST_KEYS[SIDE_TO_MOVE_KEY];
+        }
+        if let Some(file) = self.en_passant {
+            hash ^= ZOBRIST_KEYS[EN_PASSANT_KEYS + file as usize];
+        }
+        hash
+    }
+}
+
+/// Reads a move in UCI notation. cozy-chess refuses promotions to a king,
+/// which are allowed in antichess
+pub fn parse_move(uci_move: &str) -> Option<Move> {
+    match uci_move.strip_suffix('k') {
+        Some(squares) if uci_move.len() == 5 => {
+            let mut played_move: Move = squares.parse().ok()?;
+            played_move.promotion = Some(Piece::King);
+            Some(played_move)
+        }
+        _ => uci_move.parse().ok(),
+    }
+}
+
+/// Adds the move, or every promotion if a pawn reaches the last rank
+fn push_moves(moves: &mut Vec<Move>, from: Square, to: Square, is_pawn: bool) {
+    if is_pawn && (to.rank() == Rank::First || to.rank() == Rank::Eighth) {
+        for promotion in PROMOTION_PIECES {
+            moves.push(Move {
+                from,
+                to,
+                promotion: Some(promotion),
+            });
+        }
+    } else {
+        moves.push(Move {
+            from,
+            to,
+            promotion: None,
+        });
+    }
+}
+
+impl fmt::Display for AntichessBoard {
+    /// Writes the position as a FEN
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for rank in Rank::ALL.into_iter().rev() {
+            let mut empty_squares = 0;
+            for file in File::ALL {
+                let square = Square::new(file, rank);
+                match (self.piece_on(square), self.color_on(square)) {
+                    (Some(piece), Some(color)) => {
+                        if empty_squares > 0 {
+                            write!(f, "{empty_squares}")?;
+                            empty_squares = 0;
+                        }
+                        let symbol: char = piece.into();
+                        match color {
+                            Color::White => write!(f, "{}", symbol.to_ascii_uppercase())?,
+                            Color::Black => write!(f, "{symbol}")?,
+                        }
+                    }
+                    _ => empty_squares += 1,
+                }
+            }
+            if empty_squares > 0 {
+                write!(f, "{empty_squares}")?;
+            }
+            if rank != Rank::First {
+                write!(f, "/")?;
+            }
+        }
+        let side_to_move: char = self.side_to_move.into();
+        write!(f, " {side_to_move} - ")?;
+        match self.en_passant_square() {
+            Some(square) => write!(f, "{square}")?,
+            None => write!(f, "-")?,
+        }
+        write!(f, " {} {}", self.halfmove_clock, self.fullmove_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn perft(board: &AntichessBoard, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut nodes = 0;
+        for played_move in board.generate_moves() {
+            let mut child = board.clone();
+            child.play(played_move);
+            nodes += perft(&child, depth - 1);
+        }
+        nodes
+    }
+
+    #[test]
+    fn test_fen_round_trip() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1",
+            "8/8/8/8/8/8/8/8 b - - 10 40",
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b - e3 0 3",
+            "8/2K5/8/8/8/8/8/k6K w - - 0 1",
+        ];
+        for fen in fens {
+            assert_eq!(AntichessBoard::from_fen(fen).unwrap().to_string(), fen);
+        }
+        assert!(AntichessBoard::from_fen("8/8/8 w - - 0 1").is_err());
+        assert!(AntichessBoard::from_fen("P7/8/8/8/8/8/8/8 w - - 0 1").is_err());
+        assert!(AntichessBoard::from_fen("8/8/8/8/8/8/8/8 x - - 0 1").is_err());
+    }
+
+    #[test]
+    fn test_captures_are_compulsory() {
+        let mut board = AntichessBoard::startpos();
+        board.play("e2e3".parse().unwrap());
+        board.play("b7b5".parse().unwrap());
+        let moves = board.generate_moves();
+        assert_eq!(moves, vec!["f1b5".parse().unwrap()]);
+    }
+
+    #[test]
+    fn test_king_may_be_captured_and_promoted_to() {
+        let board = AntichessBoard::from_fen("8/4P3/8/8/8/8/8/3kK3 b - - 0 1").unwrap();
+        assert_eq!(board.generate_moves(), vec!["d1e1".parse().unwrap()]);
+        let board = AntichessBoard::from_fen("8/4P3/8/8/8/8/8/k7 w - - 0 1").unwrap();
+        assert!(board.is_legal(parse_move("e7e8k").unwrap()));
+        assert_eq!(parse_move("e7e8k").unwrap().to_string(), "e7e8k");
+        assert_eq!(board.generate_moves().len(), 5);
+    }
+
+    #[test]
+    fn test_no_castling() {
+        let board = AntichessBoard::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        assert!(!board.is_legal("e1g1".parse().unwrap()));
+        assert!(!board.is_legal("e1h1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_en_passant() {
+        let mut board = AntichessBoard::from_fen("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1").unwrap();
+        board.play("e2e4".parse().unwrap());
+        assert_eq!(board.generate_moves(), vec!["d4e3".parse().unwrap()]);
+        board.play("d4e3".parse().unwrap());
+        assert_eq!(board.to_string(), "4k3/8/8/8/8/4p3/8/4K3 w - - 0 2");
+        // No en passant square is recorded when no capture is possible
+        let mut board = AntichessBoard::startpos();
+        board.play("e2e4".parse().unwrap());
+        assert_eq!(
+            board.to_string(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b - - 0 1"
+        );
+    }
+
+    #[test]
+    fn test_winner() {
+        let no_pieces = AntichessBoard::from_fen("8/8/8/8/8/8/8/k7 w - - 0 1").unwrap();
+        assert_eq!(no_pieces.winner(), Some(Color::White));
+        let stalemated = AntichessBoard::from_fen("8/8/8/8/8/p7/P7/8 w - - 0 1").unwrap();
+        assert_eq!(stalemated.winner(), Some(Color::White));
+        assert_eq!(AntichessBoard::startpos().winner(), None);
+    }
+
+    #[test]
+    fn test_hash_matches_fen() {
+        let mut board = AntichessBoard::startpos();
+        for played_move in ["e2e3", "b7b5", "f1b5", "c7c6"] {
+            board.play(played_move.parse().unwrap());
+        }
+        let from_fen = AntichessBoard::from_fen(&board.to_string()).unwrap();
+        assert_eq!(board.hash(), from_fen.hash());
+        assert_ne!(board.hash(), AntichessBoard::startpos().hash());
+    }
+
+    #[test]
+    fn test_perft_startpos() {
+        let board = AntichessBoard::startpos();
+        assert_eq!(perft(&board, 1), 20);
+        assert_eq!(perft(&board, 2), 400);
+        assert_eq!(perft(&board, 3), 8067);
+        assert_eq!(perft(&board, 4), 153299);
+    }
+}
@@ -0,0 +1,131 @@
+use crate::engine_api::Engine;
+use crate::time_management::SearchLimits;
+use crate::uci_command::PositionStart;
+use crate::variant::Variant;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// The depth every position is searched to when no depth is given
+pub const DEFAULT_BENCH_DEPTH: i32 = 6;
+
+/// Positions from every stage of the game and every variant, so that a change to
+/// any part of the search changes the total node count
+const BENCH_POSITIONS: [(Variant, &str); 10] = [
+    (
+        Variant::Standard,
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    ),
+    (
+        Variant::Standard,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    ),
+    (
+        Variant::Standard,
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    ),
+    (
+        Variant::Standard,
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    ),
+    (
+        Variant::Standard,
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    ),
+    (
+        Variant::Standard,
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    ),
+    (Variant::Standard, "6k1/5ppp/8/8/8/8/5PPP/3R2K1 b - - 0 1"),
+    (Variant::Standard, "8/8/4k3/8/2P5/8/4K3/8 w - - 0 1"),
+    (
+        Variant::Antichess,
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1",
+    ),
+    (
+        Variant::Antichess,
+        "rnbqkbnr/p1pppppp/8/1p6/4P3/8/PPPP1PPP/RNBQKBNR w - b6 0 2",
+    ),
+];
+
+/// The outcome of searching every bench position
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BenchResult {
+    /// The nodes searched in each position, in the order of the positions
+    pub nodes: Vec<u64>,
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    /// The nodes searched over all positions, which only changes when the search does
+    pub fn total_nodes(&self) -> u64 {
+        self.nodes.iter().sum()
+    }
+
+    /// Nodes searched per second
+    pub fn nps(&self) -> u64 {
+        let millis = self.elapsed.as_millis().max(1);
+        (self.total_nodes() as u128 * 1000 / millis) as u64
+    }
+}
+
+/// Searches every bench position to the depth with a fresh engine,
+/// so that the node count does not depend on what was searched before
+pub fn run(depth: i32) -> Result<BenchResult, String> {
+    let limits = SearchLimits {
+        depth: Some(depth),
+        ..SearchLimits::default()
+    };
+    let start_time = Instant::now();
+    let mut nodes = Vec::new();
+    for (variant, fen) in BENCH_POSITIONS {
+        let mut engine = Engine::new();
+        engine.set_option("UCI_Variant", Some(&variant.to_string()))?;
+        engine.set_position(&PositionStart::Fen(fen.to_string()), &[])?;
+        engine.search(&limits);
+        nodes.push(engine.nodes());
+    }
+    Ok(BenchResult {
+        nodes,
+        elapsed: start_time.elapsed(),
+    })
+}
+
+/// Reads the arguments of the bench subcommand, which is only the depth
+pub fn parse_args(args: &[String]) -> Result<i32, String> {
+    match args {
+        [] => Ok(DEFAULT_BENCH_DEPTH),
+        [depth] => depth
+            .parse()
+            .ok()
+            .filter(|depth| *depth > 0)
+            .ok_or_else(|| format!("Invalid depth [{depth}]")),
+        _ => Err("bench takes only a depth".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_count_is_deterministic() {
+        let first = run(2).unwrap();
+        let second = run(2).unwrap();
+        assert_eq!(first.nodes.len(), BENCH_POSITIONS.len());
+        assert!(first.nodes.iter().all(|nodes| *nodes > 0));
+        assert_eq!(first.nodes, second.nodes);
+        assert!(run(3).unwrap().total_nodes() > first.total_nodes());
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse_args(&[]), Ok(DEFAULT_BENCH_DEPTH));
+        assert_eq!(parse_args(&["3".to_string()]), Ok(3));
+        assert!(parse_args(&["0".to_string()]).is_err());
+        assert!(parse_args(&["3".to_string(), "4".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,80 @@
+use cozy_chess::{Board, Color, FenParseError, File, Piece, Rank};
+
+/// Reads a Chess960 position from a Shredder-FEN or an X-FEN.
+/// Shredder-FEN names the file of every rook that may castle, X-FEN uses KQkq for
+/// the outermost rook on each side of the king and only names the file of a rook
+/// when another rook stands further out. cozy-chess only reads the first form,
+/// so KQkq are replaced by the files of the rooks they refer to
+pub fn from_fen(fen: &str) -> Result<Board, FenParseError> {
+    let mut fields: Vec<&str> = fen.split_whitespace().collect();
+    let castling = match fields.get(2) {
+        Some(castling) if *castling != "-" => *castling,
+        _ => return Board::from_fen(fen, true),
+    };
+    // Find where the kings and rooks stand before deciding which rooks may castle
+    fields[2] = "-";
+    let layout = Board::from_fen(&fields.join(" "), true)?;
+
+    let mut castle_rights = String::new();
+    for right in castling.chars() {
+        let color = match right.is_ascii_uppercase() {
+            true => Color::White,
+            false => Color::Black,
+        };
+        let rook_file = match right.to_ascii_lowercase() {
+            'k' => outermost_rook(&layout, color, true),
+            'q' => outermost_rook(&layout, color, false),
+            file => File::try_from(file).ok(),
+        }
+        .ok_or(FenParseError::InvalidCastlingRights)?;
+        let symbol: char = rook_file.into();
+        castle_rights.push(match color {
+            Color::White => symbol.to_ascii_uppercase(),
+            Color::Black => symbol,
+        });
+    }
+    fields[2] = &castle_rights;
+    Board::from_fen(&fields.join(" "), true)
+}
+
+/// Returns the file of the rook on the back rank furthest from the king on the given side
+fn outermost_rook(board: &Board, color: Color, short: bool) -> Option<File> {
+    let king_file = board.king(color).file();
+    let back_rank = Rank::First.relative_to(color).bitboard();
+    let rooks = board.colored_pieces(color, Piece::Rook) & back_rank;
+    let files = rooks.into_iter().map(|square| square.file());
+    if short {
+        files.filter(|file| *file > king_file).max()
+    } else {
+        files.filter(|file| *file < king_file).min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_x_fen_matches_shredder_fen() {
+        let x_fen = from_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9");
+        let shredder_fen =
+            from_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9");
+        assert_eq!(x_fen.unwrap(), shredder_fen.unwrap());
+    }
+
+    #[test]
+    fn test_x_fen_names_inner_rooks() {
+        // Both white rooks are left of the king, the inner one on b1 may castle
+        let board = from_fen("r5kr/8/8/8/8/8/8/RRK5 w Bkq - 0 1").unwrap();
+        assert_eq!(board.castle_rights(Color::White).long, Some(File::B));
+        assert_eq!(board.castle_rights(Color::Black).long, Some(File::A));
+        assert_eq!(board.castle_rights(Color::Black).short, Some(File::H));
+        assert_eq!(format!("{board:#}"), "r5kr/8/8/8/8/8/8/RRK5 w Bha - 0 1");
+    }
+
+    #[test]
+    fn test_castling_without_a_rook_is_invalid() {
+        let board = from_fen("4k3/8/8/8/8/8/8/4K3 w K - 0 1");
+        assert!(matches!(board, Err(FenParseError::InvalidCastlingRights)));
+    }
+}
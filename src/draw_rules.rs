@@ -0,0 +1,191 @@
+use cozy_chess::{BitBoard, Board, GameStatus, Piece, Rank, Square};
+
+/// The reasons a game of chess can be drawn other than stalemate
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrawReason {
+    /// Neither side has enough material left to checkmate
+    InsufficientMaterial,
+    /// The position has occurred five times
+    FivefoldRepetition,
+    /// The position has occurred three times
+    ThreefoldRepetition,
+    /// 50 moves by each player without a capture or pawn move.
+    /// cozy-chess stops the halfmove clock at 100, so the automatic
+    /// 75 move rule can not be told apart from this one
+    FiftyMoveRule,
+}
+
+impl DrawReason {
+    /// Returns true if the game is only drawn once a player claims the draw,
+    /// as opposed to the arbiter ending the game immediately
+    pub fn is_claimable(self) -> bool {
+        matches!(
+            self,
+            DrawReason::ThreefoldRepetition | DrawReason::FiftyMoveRule
+        )
+    }
+}
+
+/// Returns the key used to count repetitions of a board.
+/// Positions are only the same if the same captures are possible, so the
+/// en passant square only counts when an en passant capture can be played
+pub fn repetition_key(board: &Board) -> u64 {
+    if let Some(en_passant_file) = board.en_passant() {
+        let side_to_move = board.side_to_move();
+        let target = Square::new(en_passant_file, Rank::Sixth.relative_to(side_to_move));
+        let pawns = board.colored_pieces(side_to_move, Piece::Pawn);
+        let can_capture = board.generate_moves_for(pawns, |moves| moves.to.has(target));
+        if !can_capture {
+            return board.hash_without_ep();
+        }
+    }
+    board.hash()
+}
+
+/// Returns true if neither side can possibly checkmate, which is the case with
+/// king against king, a single minor piece against a bare king, or only
+/// bishops that all stand on squares of the same colour
+pub fn is_insufficient_material(board: &Board) -> bool {
+    let heavy_pieces =
+        board.pieces(Piece::Pawn) | board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+    if !heavy_pieces.is_empty() {
+        return false;
+    }
+    let knights = board.pieces(Piece::Knight);
+    let bishops = board.pieces(Piece::Bishop);
+    let minor_pieces = (knights | bishops).len();
+    if minor_pieces <= 1 {
+        return true;
+    }
+    knights.is_empty()
+        && (bishops.is_subset(BitBoard::DARK_SQUARES) || bishops.is_subset(BitBoard::LIGHT_SQUARES))
+}
+
+/// Returns the reason the position is drawn, if it is drawn for a reason other than stalemate.
+/// repetitions is the number of times the position has occurred, including now.
+/// Draws that end the game automatically take precedence over claimable draws
+pub fn draw_reason(board: &Board, repetitions: i32) -> Option<DrawReason> {
+    if is_insufficient_material(board) {
+        Some(DrawReason::InsufficientMaterial)
+    } else {
+        // A checkmate delivered on the last move of the move rule still counts
+        rule_draw_reason(repetitions, board.halfmove_clock(), is_checkmate(board))
+    }
+}
+
+/// Returns the reason the position is drawn by repetition or the move rule, which apply
+/// to every variant. decided is true when the last move won the game, which takes
+/// precedence over the move rule
+pub fn rule_draw_reason(repetitions: i32, halfmove_clock: u8, decided: bool) -> Option<DrawReason> {
+    if repetitions >= 5 {
+        Some(DrawReason::FivefoldRepetition)
+    } else if repetitions >= 3 {
+        Some(DrawReason::ThreefoldRepetition)
+    } else if halfmove_clock >= 100 && !decided {
+        Some(DrawReason::FiftyMoveRule)
+    } else {
+        None
+    }
+}
+
+/// Returns the status of the game, treating every draw rule as ending the game.
+/// Unlike Board::status this detects repetitions and insufficient material,
+/// and lets a checkmate on the hundredth ply stand
+pub fn game_status(board: &Board, repetitions: i32) -> GameStatus {
+    if !board.generate_moves(|_| true) {
+        if board.checkers().is_empty() {
+            return GameStatus::Drawn;
+        }
+        return GameStatus::Won;
+    }
+    match draw_reason(board, repetitions) {
+        Some(_) => GameStatus::Drawn,
+        None => GameStatus::Ongoing,
+    }
+}
+
+fn is_checkmate(board: &Board) -> bool {
+    !board.checkers().is_empty() && !board.generate_moves(|_| true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen, false).unwrap()
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        assert!(is_insufficient_material(&board(
+            "8/8/4k3/8/8/3K4/8/8 w - - 0 1"
+        )));
+        assert!(is_insufficient_material(&board(
+            "8/8/4k3/8/8/3K4/8/6N1 w - - 0 1"
+        )));
+        assert!(is_insufficient_material(&board(
+            "8/8/4k3/8/8/3K4/8/5b2 w - - 0 1"
+        )));
+        // Bishops on the same colour
+        assert!(is_insufficient_material(&board(
+            "8/8/4k1b1/8/8/3K4/8/5B2 w - - 0 1"
+        )));
+        // Bishops on opposite colours can still mate
+        assert!(!is_insufficient_material(&board(
+            "8/8/4kb2/8/8/3K4/8/5B2 w - - 0 1"
+        )));
+        assert!(!is_insufficient_material(&board(
+            "8/8/4k3/8/8/3K4/8/5NN1 w - - 0 1"
+        )));
+        assert!(!is_insufficient_material(&board(
+            "8/8/4k3/8/8/3K4/4P3/8 w - - 0 1"
+        )));
+        assert!(!is_insufficient_material(&Board::startpos()));
+    }
+
+    #[test]
+    fn test_move_rules() {
+        let fifty = board("8/8/4k3/8/8/3K4/8/R7 w - - 100 80");
+        assert_eq!(draw_reason(&fifty, 1), Some(DrawReason::FiftyMoveRule));
+        assert!(draw_reason(&fifty, 1).unwrap().is_claimable());
+        let not_yet = board("8/8/4k3/8/8/3K4/8/R7 w - - 99 80");
+        assert_eq!(draw_reason(&not_yet, 1), None);
+        // Checkmate on the hundredth ply is still checkmate
+        let mate = board("3R2k1/5ppp/8/8/8/8/5PPP/6K1 b - - 100 80");
+        assert_eq!(draw_reason(&mate, 1), None);
+        assert_eq!(game_status(&mate, 1), GameStatus::Won);
+    }
+
+    #[test]
+    fn test_repetitions() {
+        let startpos = Board::startpos();
+        assert_eq!(draw_reason(&startpos, 2), None);
+        assert_eq!(
+            draw_reason(&startpos, 3),
+            Some(DrawReason::ThreefoldRepetition)
+        );
+        assert_eq!(
+            draw_reason(&startpos, 5),
+            Some(DrawReason::FivefoldRepetition)
+        );
+        assert_eq!(game_status(&startpos, 3), GameStatus::Drawn);
+        assert_eq!(game_status(&startpos, 2), GameStatus::Ongoing);
+    }
+
+    #[test]
+    fn test_stalemate() {
+        let stalemate = board("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert_eq!(game_status(&stalemate, 1), GameStatus::Drawn);
+    }
+
+    #[test]
+    fn test_repetition_key_ignores_impossible_en_passant() {
+        let no_capture = board("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+        let without_square = board("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+        assert_eq!(repetition_key(&no_capture), repetition_key(&without_square));
+        let capture = board("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+        let capture_gone = board("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3");
+        assert_ne!(repetition_key(&capture), repetition_key(&capture_gone));
+    }
+}
@@ -1,59 +1,376 @@
-use crate::{play::Game, play::MoveEval, predicted_eval::get_truncated_eval};
-use cozy_chess::{Board, Color, GameStatus, Move};
-use std::{cmp::max, cmp::min, collections::HashMap, sync::atomic::Ordering};
+use crate::{
+    evaluator::{
+        Evaluator, EvaluatorKind, MaterialEvaluator, MobilityEvaluator, PieceSquareEvaluator,
+        RandomEvaluator,
+    },
+    handle_uci_output, handle_xboard_output,
+    play::Game,
+    play::MoveEval,
+    play::Protocol,
+    position_stack::PositionStack,
+    san,
+    time_management::SearchLimits,
+    transposition_table::{Bound, TableEntry, TranspositionTable},
+    variant::Outcome,
+};
+use cozy_chess::{Color, Move};
+use std::{
+    cmp::max,
+    cmp::min,
+    sync::atomic::{AtomicBool, Ordering},
+    sync::Arc,
+};
+
+// std::time::Instant panics on wasm32-unknown-unknown, where the time comes from the browser
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// The deepest iteration a timed search will start
+pub const MAX_SEARCH_DEPTH: i32 = 64;
+/// The depth a search without any limits stops at, unless the Depth option is set
+pub const DEFAULT_SEARCH_DEPTH: i32 = 3;
+/// At this skill the engine always plays the worst move it finds
+pub const MAX_SKILL: i32 = 20;
+/// How much better than the worst move, in centipawns, a move may be for every
+/// level of skill below MAX_SKILL and still be played
+const SKILL_MARGIN: u32 = 25;
+/// How many nodes are searched between checks of the clock
+const CLOCK_CHECK_INTERVAL: u64 = 1024;
+/// Won positions are scored as i32::MAX for white or i32::MIN for black, moved towards
+/// zero by the number of plies from the root. Any evaluation within this many plies
+/// of either end is a win
+const MAX_WIN_PLY: i32 = 1024;
+
+/// State shared by every node of a single search
+struct SearchState<'a, E: Evaluator> {
+    /// Set by the input thread when the search should stop
+    stop_search: Arc<AtomicBool>,
+    /// Asked along with the clock if the search should stop, for callers that
+    /// search on the thread that would otherwise stop the search
+    interrupt: &'a mut dyn FnMut() -> bool,
+    /// The time at which the search is aborted, if the search is timed
+    hard_deadline: Option<Instant>,
+    /// The number of positions visited so far
+    nodes: u64,
+    /// Results of earlier searches, shared between searches of the same game
+    transposition_table: &'a mut TranspositionTable,
+    /// The position being searched along with the path that led to it
+    position: PositionStack,
+    /// The deepest ply reached so far
+    seldepth: usize,
+    /// Triangular principal variation table, the line at index ply is the
+    /// expected continuation from the position at that ply
+    pv_table: Vec<Vec<Move>>,
+    /// The number of positions the search may visit
+    node_limit: Option<u64>,
+    /// If captures are forced in this search
+    forced_capture: bool,
+    /// Weither the search was aborted, in which case the current iteration is discarded
+    aborted: bool,
+    /// Scores the positions where the search stops
+    evaluator: E,
+}
+
+impl<E: Evaluator> SearchState<'_, E> {
+    /// Returns true once the search has to be abandoned
+    fn should_stop(&mut self) -> bool {
+        if !self.aborted {
+            let out_of_nodes = self.node_limit.is_some_and(|limit| self.nodes > limit);
+            let check_clock = self.nodes.is_multiple_of(CLOCK_CHECK_INTERVAL);
+            let deadline_passed = check_clock
+                && self
+                    .hard_deadline
+                    .is_some_and(|deadline| Instant::now() >= deadline);
+            let interrupted = check_clock && (self.interrupt)();
+            self.aborted = out_of_nodes
+                || deadline_passed
+                || interrupted
+                || self.stop_search.load(Ordering::SeqCst);
+        }
+        self.aborted
+    }
+
+    /// Clears the principal variation of the current ply
+    fn clear_pv(&mut self) {
+        let ply = self.position.ply();
+        if self.pv_table.len() <= ply + 1 {
+            self.pv_table.resize(ply + 2, Vec::new());
+        }
+        self.pv_table[ply].clear();
+    }
+
+    /// Sets the principal variation of the current ply to the best moves stored in the
+    /// table, following them for at most depth plies. A position cut off by an exact
+    /// entry is not searched again, so this is the only way to find its line
+    fn table_pv(&mut self, depth: i32) {
+        let ply = self.position.ply();
+        let mut board = self.position.board().clone();
+        let mut line = Vec::new();
+        while line.len() < depth as usize {
+            let entry = self.transposition_table.probe(board.hash());
+            // The legality check guards against another board with the same hash
+            match entry.and_then(|entry| entry.best_move) {
+                Some(best_move) if board.is_legal(best_move) => {
+                    line.push(best_move);
+                    board.play(best_move);
+                }
+                _ => break,
+            }
+        }
+        self.pv_table[ply] = line;
+    }
+
+    /// Sets the principal variation of the current ply to the move followed by the
+    /// principal variation of the next ply
+    fn update_pv(&mut self, best_move: Move) {
+        let ply = self.position.ply();
+        let (lines, child_lines) = self.pv_table.split_at_mut(ply + 1);
+        let line = &mut lines[ply];
+        line.clear();
+        line.push(best_move);
+        line.extend_from_slice(&child_lines[0]);
+    }
+
+    /// Returns the principal variation starting with the given root move
+    fn root_pv(&self, root_move: Move) -> Vec<Move> {
+        let mut line = vec![root_move];
+        if let Some(child_line) = self.pv_table.get(1) {
+            line.extend_from_slice(child_line);
+        }
+        line
+    }
+}
+
+/// Returns true if the evaluation is better than the best evaluation
+/// for the side to move, recalling that white minimizes and black maximizes
+fn is_improvement(side_to_move: Color, evaluation: i32, best_evaluation: i32) -> bool {
+    match side_to_move {
+        Color::Black => evaluation > best_evaluation,
+        Color::White => evaluation < best_evaluation,
+    }
+}
+
+/// Returns the evaluation of a position the winner has won, reached the given number
+/// of plies from the root of the search, so that a quicker win scores higher
+fn win_evaluation(winner: Color, ply: i32) -> i32 {
+    match winner {
+        Color::White => i32::MAX - ply,
+        Color::Black => i32::MIN + ply,
+    }
+}
+
+/// Returns the winner and the number of plies from the root to the end of the game,
+/// if the evaluation is of a won position
+pub fn win_distance(evaluation: i32) -> Option<(Color, i32)> {
+    if evaluation >= i32::MAX - MAX_WIN_PLY {
+        Some((Color::White, i32::MAX - evaluation))
+    } else if evaluation <= i32::MIN + MAX_WIN_PLY {
+        Some((Color::Black, evaluation - i32::MIN))
+    } else {
+        None
+    }
+}
+
+/// Counts the plies to a win from the position at the given ply instead of from the root,
+/// as the same position may be found again at another ply
+fn to_table_evaluation(evaluation: i32, ply: i32) -> i32 {
+    match win_distance(evaluation) {
+        Some((Color::White, _)) => evaluation + ply,
+        Some((Color::Black, _)) => evaluation - ply,
+        None => evaluation,
+    }
+}
+
+/// Counts the plies to a win stored in the table from the root again
+fn from_table_evaluation(evaluation: i32, ply: i32) -> i32 {
+    match win_distance(evaluation) {
+        Some((Color::White, _)) => evaluation - ply,
+        Some((Color::Black, _)) => evaluation + ply,
+        None => evaluation,
+    }
+}
 
 /// Returns a legal move that places the player to move in the worst position
 /// Additionally returns the evaluation after said move
-pub fn get_move(game: Game) -> Game {
-    let older_binding = game.current_best_move.clone().unwrap();
-    let mut binding = older_binding.lock().unwrap();
-    let mut move_list = get_move_depth_1(game.clone());
+pub fn get_move(game: Game, limits: &SearchLimits) -> Game {
+    get_move_interruptible(game, limits, &mut || false)
+}
+
+/// Searches like get_move, stopping early once interrupt returns true.
+/// interrupt is called every CLOCK_CHECK_INTERVAL nodes, so searches that can not be stopped
+/// from another thread, such as in a browser, can still be cut short
+pub fn get_move_interruptible(
+    game: Game,
+    limits: &SearchLimits,
+    interrupt: &mut dyn FnMut() -> bool,
+) -> Game {
+    match game.evaluator {
+        EvaluatorKind::PieceSquare => search_with(game, limits, interrupt, PieceSquareEvaluator),
+        EvaluatorKind::Material => search_with(game, limits, interrupt, MaterialEvaluator),
+        EvaluatorKind::Random => search_with(game, limits, interrupt, RandomEvaluator),
+        EvaluatorKind::Mobility => search_with(game, limits, interrupt, MobilityEvaluator),
+    }
+}
+
+/// Searches with the evaluator chosen for the game. The search is built for each
+/// evaluator, so evaluating a position is not a dynamic call
+fn search_with<E: Evaluator>(
+    game: Game,
+    limits: &SearchLimits,
+    interrupt: &mut dyn FnMut() -> bool,
+    evaluator: E,
+) -> Game {
+    let start_time = Instant::now();
     let side_to_move = game.board.side_to_move();
-    let tmp_best_move = match side_to_move {
-        Color::White => move_list.first().unwrap().clone(),
-        Color::Black => move_list.last().unwrap().clone(),
+    let time_budget = limits.time_budget(side_to_move);
+    let max_depth = match limits.depth {
+        Some(depth) => depth.clamp(1, MAX_SEARCH_DEPTH),
+        None if limits.infinite || limits.nodes.is_some() || time_budget.is_some() => {
+            MAX_SEARCH_DEPTH
+        }
+        None => game.depth,
     };
+    let table_binding = game.transposition_table.clone();
+    let mut transposition_table = table_binding.lock().unwrap();
+    let mut search = SearchState {
+        transposition_table: &mut transposition_table,
+        stop_search: game.stop_search.clone(),
+        interrupt,
+        hard_deadline: time_budget.map(|budget| start_time + budget.hard),
+        nodes: 0,
+        position: PositionStack::new(game.board.clone(), &game.previous_boards),
+        seldepth: 0,
+        pv_table: Vec::new(),
+        node_limit: limits.nodes,
+        forced_capture: game.forced_capture,
+        aborted: false,
+        evaluator,
+    };
+    let older_binding = game.current_best_move.clone().unwrap();
+    let mut binding = older_binding.lock().unwrap();
+    let mut move_list = get_move_depth_1(&mut search);
+    let seed = game.board.hash();
+    let margin = skill_margin(game.skill);
+    let tmp_best_move = weakened_move(&move_list, side_to_move, game.skill, seed);
 
     *binding = Option::Some(tmp_best_move);
     drop(binding);
-    for depth in 1..=3 {
-        if game.stop_search.load(Ordering::SeqCst) {
+    for depth in 1..=max_depth {
+        if search.should_stop() {
             break;
         }
-        move_list = get_move_iterative(game.clone(), depth, move_list);
-        let mut binding = older_binding.lock().unwrap();
-        let side_to_move = game.board.side_to_move();
-        let tmp_best_move = match side_to_move {
-            Color::White => move_list.first().unwrap().clone(),
-            Color::Black => move_list.last().unwrap().clone(),
+        if time_budget.is_some_and(|budget| start_time.elapsed() >= budget.soft) {
+            break;
+        }
+        move_list = match get_move_iterative(depth, move_list.clone(), margin, &mut search) {
+            Some(move_list) => move_list,
+            None => break,
         };
+        let mut binding = older_binding.lock().unwrap();
+        let tmp_best_move = weakened_move(&move_list, side_to_move, game.skill, seed);
+        let pv = handle_uci_output::uci_line(&game.board, &tmp_best_move.pv, game.chess960);
+        match game.protocol {
+            Protocol::Uci => {
+                let score = handle_uci_output::score(tmp_best_move.evaluation, side_to_move);
+                handle_uci_output::search_info(
+                    depth,
+                    search.seldepth as i32,
+                    &score,
+                    search.nodes,
+                    start_time.elapsed(),
+                    &pv,
+                );
+                if game.debug_mode {
+                    let line = san::encode_line(&game.board, &tmp_best_move.pv);
+                    handle_uci_output::info_string(&format!("Depth {depth} line {line}"));
+                }
+            }
+            Protocol::Xboard { post: true } => handle_xboard_output::thinking(
+                depth,
+                handle_xboard_output::score(tmp_best_move.evaluation, side_to_move),
+                start_time.elapsed(),
+                search.nodes,
+                &pv,
+            ),
+            Protocol::Xboard { post: false } | Protocol::Embedded => (),
+        }
         *binding = Option::Some(tmp_best_move);
         drop(binding);
     }
-    game
+    Game {
+        nodes: search.nodes,
+        ..game
+    }
 }
 
-fn get_move_iterative(game: Game, depth: i32, mut move_list: Vec<MoveEval>) -> Vec<MoveEval> {
-    let side_to_move = game.board.side_to_move();
+/// Returns how much better than the worst move a move may be at the skill
+fn skill_margin(skill: i32) -> u32 {
+    (MAX_SKILL - skill).max(0) as u32 * SKILL_MARGIN
+}
+
+/// Chooses one of the moves close enough to the worst move for the skill,
+/// at MAX_SKILL this is always the worst move.
+/// The choice depends on the position, so the same position is always answered the same way
+fn weakened_move(move_list: &[MoveEval], side_to_move: Color, skill: i32, seed: u64) -> MoveEval {
+    let worst = match side_to_move {
+        Color::White => move_list.first().unwrap(),
+        Color::Black => move_list.last().unwrap(),
+    };
+    let margin = skill_margin(skill);
+    if margin == 0 {
+        return worst.clone();
+    }
+    let candidates: Vec<&MoveEval> = move_list
+        .iter()
+        .filter(|move_eval| move_eval.evaluation.abs_diff(worst.evaluation) <= margin)
+        .collect();
+    candidates[(seed % candidates.len() as u64) as usize].clone()
+}
+
+/// Evaluates every move in the move list to the given depth.
+/// Moves within margin of the worst move are evaluated exactly, so that a weakened
+/// search can choose between them, the others may only be bounds
+/// Returns None if the search was aborted before the iteration finished
+fn get_move_iterative<E: Evaluator>(
+    depth: i32,
+    mut move_list: Vec<MoveEval>,
+    margin: u32,
+    search: &mut SearchState<E>,
+) -> Option<Vec<MoveEval>> {
+    let side_to_move = search.position.board().side_to_move();
     if side_to_move == Color::Black {
         move_list.reverse()
     }
+    // The window is kept just wider than the margin, so that a bound is never mistaken
+    // for a move within the margin
+    let widening = match margin {
+        0 => 0,
+        margin => margin + 1,
+    };
     let mut alpha = i32::MIN;
     let mut beta = i32::MAX;
     let mut new_move_list = Vec::new();
     for move_to_play in move_list {
+        let (window_alpha, window_beta) = match side_to_move {
+            Color::Black => (alpha.saturating_sub_unsigned(widening), beta),
+            Color::White => (alpha, beta.saturating_add_unsigned(widening)),
+        };
         let evaluation = get_move_evaluation(
             move_to_play.best_move,
-            &game.board,
             depth,
-            alpha,
-            beta,
-            &game.previous_boards,
-            game.forced_capture,
+            window_alpha,
+            window_beta,
+            search,
         );
+        if search.aborted {
+            return None;
+        }
         new_move_list.push(MoveEval {
-            evaluation: evaluation,
+            evaluation,
             best_move: move_to_play.best_move,
+            pv: search.root_pv(move_to_play.best_move),
         });
         match side_to_move {
             Color::Black => {
@@ -70,224 +387,427 @@ fn get_move_iterative(game: Game, depth: i32, mut move_list: Vec<MoveEval>) -> V
             }
         }
     }
-    new_move_list.sort_by(|a, b| a.evaluation.cmp(&b.evaluation));
-    return new_move_list;
+    new_move_list.sort_by_key(|a| a.evaluation);
+    Some(new_move_list)
 }
 
-fn get_move_depth_1(game: Game) -> Vec<MoveEval> {
-    let side_to_move = game.board.side_to_move();
-    let enemy_pieces = game.board.colors(!side_to_move);
-    let mut capture_moves: Vec<Move> = Vec::new();
+fn get_move_depth_1<E: Evaluator>(search: &mut SearchState<E>) -> Vec<MoveEval> {
+    let moves = search.position.board().legal_moves(search.forced_capture);
     let mut move_list: Vec<MoveEval> = Vec::new();
-    if game.forced_capture {
-        game.board.generate_moves(|moves| {
-            let mut captures = moves.clone();
-            captures.to &= enemy_pieces;
-            for mv in captures {
-                capture_moves.push(mv);
-            }
-            false
-        });
-        if !capture_moves.is_empty() {
-            for mv in capture_moves {
-                let cur_eval = get_move_evaluation(
-                    mv,
-                    &game.board,
-                    0,
-                    i32::MIN,
-                    i32::MAX,
-                    &game.previous_boards,
-                    game.forced_capture,
-                );
-                let move_eval = MoveEval {
-                    evaluation: cur_eval,
-                    best_move: mv,
-                };
-                move_list.push(move_eval);
-            }
-            move_list.sort_by(|a, b| a.evaluation.cmp(&b.evaluation));
-            return move_list;
-        }
+    for mv in moves {
+        let cur_eval = get_move_evaluation(mv, 0, i32::MIN, i32::MAX, search);
+        let move_eval = MoveEval {
+            evaluation: cur_eval,
+            best_move: mv,
+            pv: vec![mv],
+        };
+        move_list.push(move_eval);
     }
-
-    game.board.generate_moves(|moves| {
-        for mv in moves {
-            let cur_eval = get_move_evaluation(
-                mv,
-                &game.board,
-                0,
-                i32::MIN,
-                i32::MAX,
-                &game.previous_boards,
-                game.forced_capture,
-            );
-            let move_eval = MoveEval {
-                evaluation: cur_eval,
-                best_move: mv,
-            };
-            move_list.push(move_eval);
-        }
-        false
-    });
-    move_list.sort_by(|a, b| a.evaluation.cmp(&b.evaluation));
-    return move_list;
+    move_list.sort_by_key(|a| a.evaluation);
+    move_list
 }
 
 /// Returns the evaluation for a specific move. Assumes players will pick the move that hurts them the most
 /// depth = the amount of ply to search down. 0 is base case, 1 makes opponent move and stops
 /// alpha = minimum score that the maximizing player is assured of
 /// beta = maximum score that the minimizing player is assured of.
-fn get_move_evaluation(
+fn get_move_evaluation<E: Evaluator>(
     piece_move: Move,
-    board: &Board,
     depth: i32,
     alpha: i32,
     beta: i32,
-    previous_boards: &HashMap<u64, i32>,
-    forced_capture: bool,
+    search: &mut SearchState<E>,
 ) -> i32 {
-    let mut board_with_move = board.clone();
-    let mut prev_boards = previous_boards.clone(); //TODO: Make / Unmake
-    board_with_move.play(piece_move);
-    let board_hash = board_with_move.hash();
-    prev_boards
-        .entry(board_hash)
-        .and_modify(|board_hash| *board_hash += 1)
-        .or_insert(1);
-    get_board_evaluation(
-        &board_with_move,
-        depth,
-        alpha,
-        beta,
-        &prev_boards,
-        forced_capture,
-    )
+    search
+        .evaluator
+        .make_move(search.position.board(), piece_move);
+    search.position.play(piece_move);
+    search.seldepth = max(search.seldepth, search.position.ply());
+    let evaluation = get_board_evaluation(depth, alpha, beta, search);
+    search.position.undo();
+    search.evaluator.unmake_move();
+    evaluation
 }
 
-/// Returns the evaluation for white for a specific move. Assumes players will pick the move that hurts them the most
+/// Returns the evaluation for white of the current position of the search.
+/// Assumes players will pick the move that hurts them the most
 /// depth = the amount of ply to search down. 0 is base case, 1 makes opponent move and stops
 /// alpha = minimum score that the maximizing player is assured of
 /// beta = maximum score that the minimizing player is assured of.
-fn get_board_evaluation(
-    board: &Board,
+fn get_board_evaluation<E: Evaluator>(
     depth: i32,
     mut alpha: i32,
     mut beta: i32,
-    previous_boards: &HashMap<u64, i32>,
-    forced_capture: bool,
+    search: &mut SearchState<E>,
 ) -> i32 {
-    // Deal with game ending evaluation
-    for val in previous_boards.values() {
-        if *val >= 3 {
-            return 0; // 3 fold reprition, stop
-        }
+    search.nodes += 1;
+    search.clear_pv();
+    if search.should_stop() {
+        return 0;
     }
 
-    match board.status() {
-        GameStatus::Drawn => return 0,
-        GameStatus::Won => match board.side_to_move() {
-            // Recall loser is current side to move
-            Color::Black => {
-                return i32::MAX;
-            }
-            Color::White => {
-                return i32::MIN;
-            }
-        },
-        GameStatus::Ongoing => (),
-    }
-    if board.halfmove_clock() >= 50 {
-        return 0;
+    // Deal with game ending evaluation
+    let board = search.position.board();
+    let ply = search.position.ply() as i32;
+    match board.outcome(search.position.repetitions()) {
+        Some(Outcome::Draw) => return 0,
+        Some(Outcome::Winner(winner)) => return win_evaluation(winner, ply),
+        None => (),
     }
     if depth == 0 {
-        let mut evaluation = 0;
-        evaluation += get_truncated_eval(board);
-        return evaluation;
+        return search.evaluator.evaluate(board);
     }
 
     // Recursive evaluation
 
+    let key = board.hash();
+    let table_entry = search
+        .transposition_table
+        .probe(key)
+        .map(|entry| TableEntry {
+            evaluation: from_table_evaluation(entry.evaluation, ply),
+            ..entry
+        });
+    if let Some(entry) = table_entry {
+        if let Some(evaluation) = entry.cutoff(depth, alpha, beta) {
+            if entry.bound == Bound::Exact {
+                search.table_pv(depth);
+            }
+            return evaluation;
+        }
+    }
+
     let side_to_move = board.side_to_move();
-    let enemy_pieces = board.colors(!side_to_move);
-    let mut capture_moves: Vec<Move> = Vec::new();
+    let mut moves = board.legal_moves(search.forced_capture);
+    // Search the move that was best last time first, as it is likely to cause a cutoff
+    if let Some(hash_move) = table_entry.and_then(|entry| entry.best_move) {
+        if let Some(index) = moves.iter().position(|mv| *mv == hash_move) {
+            moves[..=index].rotate_right(1);
+        }
+    }
 
+    let alpha_original = alpha;
+    let beta_original = beta;
     let mut evaluation = match side_to_move {
         Color::Black => i32::MIN,
         Color::White => i32::MAX,
     };
+    let mut best_move: Option<Move> = None;
 
-    // Handle case of forced capture
-    if forced_capture {
-        board.generate_moves(|moves| {
-            let mut captures = moves.clone();
-            captures.to &= enemy_pieces;
-            for mv in captures {
-                capture_moves.push(mv);
-            }
-            false
-        });
-        if !capture_moves.is_empty() {
-            for mv in capture_moves {
-                let cur_eval = get_move_evaluation(
-                    mv,
-                    board,
-                    depth - 1,
-                    alpha,
-                    beta,
-                    previous_boards,
-                    forced_capture,
-                );
-                match side_to_move {
-                    Color::Black => {
-                        evaluation = max(cur_eval, evaluation);
-                        alpha = max(alpha, evaluation);
-                        if evaluation >= beta {
-                            return evaluation;
-                        }
-                    }
-                    Color::White => {
-                        evaluation = min(cur_eval, evaluation);
-                        beta = min(beta, evaluation);
-                        if evaluation <= alpha {
-                            return evaluation;
-                        }
-                    }
-                }
-            }
+    for mv in moves {
+        let cur_eval = get_move_evaluation(mv, depth - 1, alpha, beta, search);
+        if search.aborted {
             return evaluation;
         }
-    }
-
-    // Handle case of any legal move is valid
-    board.generate_moves(|moves| {
-        for mv in moves {
-            let cur_eval = get_move_evaluation(
-                mv,
-                board,
-                depth - 1,
-                alpha,
-                beta,
-                previous_boards,
-                forced_capture,
-            );
-            match side_to_move {
-                Color::Black => {
-                    evaluation = max(cur_eval, evaluation);
-                    alpha = max(alpha, evaluation);
-                    if evaluation >= beta {
-                        return true;
-                    }
+        if best_move.is_none() || is_improvement(side_to_move, cur_eval, evaluation) {
+            best_move = Some(mv);
+            search.update_pv(mv);
+        }
+        match side_to_move {
+            Color::Black => {
+                evaluation = max(cur_eval, evaluation);
+                alpha = max(alpha, evaluation);
+                if evaluation >= beta {
+                    break;
                 }
-                Color::White => {
-                    evaluation = min(cur_eval, evaluation);
-                    beta = min(beta, evaluation);
-                    if evaluation <= alpha {
-                        return true;
-                    }
+            }
+            Color::White => {
+                evaluation = min(cur_eval, evaluation);
+                beta = min(beta, evaluation);
+                if evaluation <= alpha {
+                    break;
                 }
             }
         }
-        false
+    }
+
+    let bound = if evaluation <= alpha_original {
+        Bound::Upper
+    } else if evaluation >= beta_original {
+        Bound::Lower
+    } else {
+        Bound::Exact
+    };
+    search.transposition_table.store(TableEntry {
+        key,
+        depth,
+        bound,
+        evaluation: to_table_evaluation(evaluation, ply),
+        best_move,
     });
-    return evaluation;
+    evaluation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antichess::AntichessBoard;
+    use crate::handle_uci_input::default_game;
+    use crate::time_management::parse_go;
+    use crate::variant::{Variant, VariantBoard};
+    use cozy_chess::Board;
+    use std::sync::Mutex;
+
+    fn search(fen: &str, go_tokens: &[&str]) -> MoveEval {
+        search_variant(fen, Variant::Standard, go_tokens)
+    }
+
+    fn search_variant(fen: &str, variant: Variant, go_tokens: &[&str]) -> MoveEval {
+        let game = Game {
+            variant,
+            board: VariantBoard::from_fen(fen, variant).unwrap(),
+            current_best_move: Some(Arc::new(Mutex::new(None))),
+            ..default_game()
+        };
+        let game = get_move(game, &parse_go(go_tokens));
+        let best_move = game.current_best_move.unwrap();
+        let move_eval = best_move.lock().unwrap().clone();
+        move_eval.unwrap()
+    }
+
+    #[test]
+    fn test_depth_limited_search_plays_only_legal_move() {
+        let move_eval = search("7k/8/8/8/8/8/6q1/7K w - - 0 1", &["go", "depth", "2"]);
+        assert_eq!(move_eval.best_move, "h1g2".parse().unwrap());
+    }
+
+    #[test]
+    fn test_node_limited_search_is_reproducible() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let first = search(fen, &["go", "nodes", "5000"]);
+        let second = search(fen, &["go", "nodes", "5000"]);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_principal_variation_is_playable() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let move_eval = search(fen, &["go", "depth", "3"]);
+        assert_eq!(move_eval.pv.first(), Some(&move_eval.best_move));
+        assert_eq!(move_eval.pv.len(), 4);
+        let mut board: Board = fen.parse().unwrap();
+        for pv_move in move_eval.pv {
+            assert!(board.try_play(pv_move).is_ok(), "{pv_move} is illegal");
+        }
+    }
+
+    #[test]
+    fn test_exact_table_hits_keep_the_line() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let game = Game {
+            board: VariantBoard::from_fen(fen, Variant::Standard).unwrap(),
+            current_best_move: Some(Arc::new(Mutex::new(None))),
+            ..default_game()
+        };
+        let limits = parse_go(&["go", "depth", "3"]);
+        let first = get_move(game, &limits);
+        let first_move = first.current_best_move.clone().unwrap();
+        let first_move = first_move.lock().unwrap().clone().unwrap();
+        // The second search finds every position of the first in the table
+        let second = get_move(first.clone(), &limits);
+        assert!(second.nodes < first.nodes);
+        let second_move = second.current_best_move.unwrap().lock().unwrap().clone();
+        let second_move = second_move.unwrap();
+        assert_eq!(second_move.evaluation, first_move.evaluation);
+        assert_eq!(second_move.pv.len(), 4);
+        let mut board: Board = fen.parse().unwrap();
+        for pv_move in second_move.pv {
+            assert!(board.try_play(pv_move).is_ok(), "{pv_move} is illegal");
+        }
+    }
+
+    #[test]
+    fn test_antichess_search_keeps_pieces() {
+        let fen = "1r6/8/8/8/8/8/7P/R7 w - - 0 1";
+        let move_eval = search_variant(fen, Variant::Antichess, &["go", "depth", "2"]);
+        let mut board = AntichessBoard::from_fen(fen).unwrap();
+        board.play(move_eval.best_move);
+        // Giving a piece away would be good for white, so the engine does not
+        let replies = board.generate_moves();
+        assert!(replies
+            .iter()
+            .all(|reply| board.piece_on(reply.to).is_none()));
+    }
+
+    #[test]
+    fn test_antichess_losing_every_piece_wins() {
+        let fen = "4r3/8/8/8/8/8/8/4R3 w - - 0 1";
+        let move_eval = search_variant(fen, Variant::Antichess, &["go", "depth", "1"]);
+        assert_eq!(move_eval.best_move, "e1e8".parse().unwrap());
+        assert_eq!(move_eval.evaluation, i32::MIN + 1);
+    }
+
+    #[test]
+    fn test_win_distance_is_counted_from_the_root() {
+        // Ra8 forces Rxa8, and Bxa8 then takes black's last piece on the third ply
+        let fen = "7r/8/8/8/4B3/8/8/R7 w - - 0 1";
+        for depth in ["3", "4", "5"] {
+            let move_eval = search_variant(fen, Variant::Antichess, &["go", "depth", depth]);
+            assert_eq!(win_distance(move_eval.evaluation), Some((Color::Black, 3)));
+        }
+    }
+
+    #[test]
+    fn test_win_distance() {
+        assert_eq!(
+            win_distance(win_evaluation(Color::White, 3)),
+            Some((Color::White, 3))
+        );
+        assert_eq!(
+            win_distance(win_evaluation(Color::Black, 0)),
+            Some((Color::Black, 0))
+        );
+        assert_eq!(win_distance(-350), None);
+        // A win stored at one ply and found at another keeps its distance from the position
+        let stored = to_table_evaluation(win_evaluation(Color::Black, 5), 2);
+        assert_eq!(
+            from_table_evaluation(stored, 4),
+            win_evaluation(Color::Black, 7)
+        );
+        assert_eq!(from_table_evaluation(to_table_evaluation(120, 2), 4), 120);
+    }
+
+    #[test]
+    fn test_weakened_move_stays_near_the_worst_move() {
+        let move_eval = |evaluation, uci_move: &str| MoveEval {
+            evaluation,
+            best_move: uci_move.parse().unwrap(),
+            pv: Vec::new(),
+        };
+        let move_list = [
+            move_eval(-300, "a2a3"),
+            move_eval(-150, "b2b3"),
+            move_eval(400, "c2c3"),
+        ];
+        for seed in 0..10 {
+            let chosen = weakened_move(&move_list, Color::White, MAX_SKILL, seed);
+            assert_eq!(chosen.evaluation, -300);
+            let chosen = weakened_move(&move_list, Color::White, 10, seed);
+            assert!(chosen.evaluation < 0);
+            let chosen = weakened_move(&move_list, Color::Black, 0, seed);
+            assert_eq!(chosen.evaluation, 400);
+        }
+        let choices: Vec<i32> = (0..10)
+            .map(|seed| weakened_move(&move_list, Color::White, 10, seed).evaluation)
+            .collect();
+        assert!(choices.contains(&-300) && choices.contains(&-150));
+    }
+
+    #[test]
+    fn test_weakened_search_chooses_from_exact_evaluations() {
+        let board = VariantBoard::startpos(Variant::Standard);
+        let skill = 12;
+        let game = Game {
+            skill,
+            board: board.clone(),
+            current_best_move: Some(Arc::new(Mutex::new(None))),
+            ..default_game()
+        };
+        let game = get_move(game, &parse_go(&["go", "depth", "3"]));
+        let chosen = game.current_best_move.unwrap().lock().unwrap().clone();
+        let chosen = chosen.unwrap();
+        assert_eq!(chosen.pv.first(), Some(&chosen.best_move));
+        // Every move searched again on its own, without a window
+        let mut search = SearchState {
+            transposition_table: &mut TranspositionTable::new(1),
+            stop_search: Arc::new(AtomicBool::new(false)),
+            interrupt: &mut || false,
+            hard_deadline: None,
+            nodes: 0,
+            position: PositionStack::new(board.clone(), &Default::default()),
+            seldepth: 0,
+            pv_table: Vec::new(),
+            node_limit: None,
+            forced_capture: true,
+            aborted: false,
+            evaluator: PieceSquareEvaluator,
+        };
+        let exact: Vec<(Move, i32)> = board
+            .legal_moves(true)
+            .into_iter()
+            .map(|mv| {
+                (
+                    mv,
+                    get_move_evaluation(mv, 3, i32::MIN, i32::MAX, &mut search),
+                )
+            })
+            .collect();
+        let worst = exact
+            .iter()
+            .map(|(_, evaluation)| *evaluation)
+            .min()
+            .unwrap();
+        assert!(exact.contains(&(chosen.best_move, chosen.evaluation)));
+        assert!(chosen.evaluation.abs_diff(worst) <= skill_margin(skill));
+    }
+
+    /// Counts how deep the search has played moves, to check that every move is taken back
+    struct PlyCounter {
+        ply: i32,
+        deepest: i32,
+    }
+
+    impl Evaluator for PlyCounter {
+        fn evaluate(&mut self, board: &VariantBoard) -> i32 {
+            board.evaluate()
+        }
+
+        fn make_move(&mut self, _board: &VariantBoard, _played_move: Move) {
+            self.ply += 1;
+            self.deepest = max(self.deepest, self.ply);
+        }
+
+        fn unmake_move(&mut self) {
+            self.ply -= 1;
+        }
+    }
+
+    #[test]
+    fn test_evaluator_hooks_follow_the_search() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let mut search = SearchState {
+            transposition_table: &mut TranspositionTable::default(),
+            stop_search: Arc::new(AtomicBool::new(false)),
+            interrupt: &mut || false,
+            hard_deadline: None,
+            nodes: 0,
+            position: PositionStack::new(
+                VariantBoard::from_fen(fen, Variant::Standard).unwrap(),
+                &Default::default(),
+            ),
+            seldepth: 0,
+            pv_table: Vec::new(),
+            node_limit: None,
+            forced_capture: false,
+            aborted: false,
+            evaluator: PlyCounter { ply: 0, deepest: 0 },
+        };
+        let move_list = get_move_depth_1(&mut search);
+        get_move_iterative(2, move_list, 0, &mut search).unwrap();
+        assert_eq!(search.evaluator.ply, 0);
+        assert_eq!(search.evaluator.deepest, 3);
+    }
+
+    #[test]
+    fn test_every_evaluator_finds_a_legal_move() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let board: Board = fen.parse().unwrap();
+        for evaluator in EvaluatorKind::ALL {
+            let game = Game {
+                evaluator,
+                board: VariantBoard::Standard(board.clone()),
+                current_best_move: Some(Arc::new(Mutex::new(None))),
+                ..default_game()
+            };
+            let game = get_move(game, &parse_go(&["go", "depth", "2"]));
+            let move_eval = game
+                .current_best_move
+                .unwrap()
+                .lock()
+                .unwrap()
+                .clone()
+                .unwrap();
+            assert!(board.is_legal(move_eval.best_move), "{evaluator}");
+        }
+    }
 }
@@ -0,0 +1,270 @@
+use crate::engine::{get_move, get_move_interruptible};
+use crate::handle_uci_input::{
+    apply_option, default_game, parse_uci_move, set_position, uci_new_game,
+};
+use crate::perft;
+use crate::play::{Game, MoveEval, Protocol};
+use crate::time_management::SearchLimits;
+use crate::uci_command::PositionStart;
+use crate::variant::{Outcome, VariantBoard};
+use cozy_chess::Move;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// The engine for programs that use it directly rather than over UCI.
+/// A search either blocks with search, or runs on its own thread after
+/// start_search until it reaches its limits or stop is called.
+/// Evaluations are in centipawns for white, with a won game for white as i32::MAX
+/// and a won game for black as i32::MIN, less the plies to the end of the game
+pub struct Engine {
+    game: Game,
+    /// The thread of a search started with start_search
+    search_thread: Option<JoinHandle<Game>>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
+
+impl Engine {
+    /// Creates an engine set up to play standard chess from the starting position
+    pub fn new() -> Engine {
+        Engine {
+            game: Game {
+                protocol: Protocol::Embedded,
+                ..default_game()
+            },
+            search_thread: None,
+        }
+    }
+
+    /// Starts a new game from the starting position, keeping the options that were set
+    pub fn new_game(&mut self) {
+        self.stop();
+        self.game = uci_new_game(self.game.clone());
+    }
+
+    /// Sets an option by its UCI name, such as Hash or UCI_Variant
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), String> {
+        self.stop();
+        self.game = apply_option(name, value, &self.game)?;
+        Ok(())
+    }
+
+    /// Sets the position after playing the moves, given in UCI notation, from the start.
+    /// The position is left unchanged if the FEN or any move is invalid
+    pub fn set_position(&mut self, start: &PositionStart, moves: &[String]) -> Result<(), String> {
+        self.stop();
+        self.game = set_position(start, moves, &self.game)?;
+        Ok(())
+    }
+
+    /// Plays a move, given in UCI notation, on the current position
+    pub fn play_move(&mut self, uci_move: &str) -> Result<Move, String> {
+        self.stop();
+        let board = &mut self.game.board;
+        let played_move = parse_uci_move(board, uci_move, self.game.chess960)
+            .ok_or_else(|| format!("Illegal move [{uci_move}] in position {board}"))?;
+        board.play(played_move);
+        self.game
+            .previous_boards
+            .entry(board.repetition_key())
+            .and_modify(|count| *count += 1)
+            .or_insert(1);
+        Ok(played_move)
+    }
+
+    /// The position the engine will search
+    pub fn board(&self) -> &VariantBoard {
+        &self.game.board
+    }
+
+    /// Weither moves are read and written as Chess960 moves
+    pub fn chess960(&self) -> bool {
+        self.game.chess960
+    }
+
+    /// The static evaluation of the position in centipawns for white,
+    /// as the evaluator chosen with the Evaluator option sees it
+    pub fn evaluate(&self) -> i32 {
+        self.game.evaluator.evaluate(&self.game.board)
+    }
+
+    /// Counts the positions below every legal move to the given depth,
+    /// following the ForcedCapture option and the rules of the variant
+    pub fn divide(&self, depth: u32) -> Vec<(Move, u64)> {
+        perft::divide(&self.game.board, depth, self.game.forced_capture)
+    }
+
+    /// The number of positions visited by the last search
+    pub fn nodes(&self) -> u64 {
+        self.game.nodes
+    }
+
+    /// How the game ended, or None if it is still being played
+    pub fn outcome(&self) -> Option<Outcome> {
+        let board = &self.game.board;
+        let repetitions = self
+            .game
+            .previous_boards
+            .get(&board.repetition_key())
+            .copied()
+            .unwrap_or(1);
+        board.outcome(repetitions)
+    }
+
+    /// Searches the position until the limits are reached and returns the result.
+    /// Returns None if the game is over. An infinite search never finishes on its own,
+    /// so it should be started with start_search instead
+    pub fn search(&mut self, limits: &SearchLimits) -> Option<MoveEval> {
+        if !self.prepare_search() {
+            return None;
+        }
+        self.game = get_move(self.game.clone(), limits);
+        self.best_move()
+    }
+
+    /// Searches like search without leaving the calling thread, stopping early
+    /// once interrupt returns true. This is how a search is stopped where
+    /// threads are not available, such as in a browser
+    pub fn search_interruptible(
+        &mut self,
+        limits: &SearchLimits,
+        interrupt: &mut dyn FnMut() -> bool,
+    ) -> Option<MoveEval> {
+        if !self.prepare_search() {
+            return None;
+        }
+        self.game = get_move_interruptible(self.game.clone(), limits, interrupt);
+        self.best_move()
+    }
+
+    /// Starts searching the position on another thread.
+    /// Returns false if the game is over, in which case there is nothing to search
+    pub fn start_search(&mut self, limits: SearchLimits) -> bool {
+        if !self.prepare_search() {
+            return false;
+        }
+        let game = self.game.clone();
+        self.search_thread = Some(thread::spawn(move || get_move(game, &limits)));
+        true
+    }
+
+    /// Weither a search started with start_search is still running
+    pub fn is_searching(&self) -> bool {
+        self.search_thread
+            .as_ref()
+            .is_some_and(|search_thread| !search_thread.is_finished())
+    }
+
+    /// The best move found by the current or last search, along with its
+    /// evaluation and principal variation
+    pub fn best_move(&self) -> Option<MoveEval> {
+        let current_best_move = self.game.current_best_move.as_ref()?;
+        current_best_move.lock().unwrap().clone()
+    }
+
+    /// Stops a running search, waiting for it to finish, and returns its result
+    pub fn stop(&mut self) -> Option<MoveEval> {
+        let search_thread = self.search_thread.take()?;
+        self.game.stop_search.store(true, Ordering::SeqCst);
+        self.game = search_thread.join().unwrap();
+        self.best_move()
+    }
+
+    /// Stops any running search and clears the result of the last one.
+    /// Returns false if the side to move has no moves
+    fn prepare_search(&mut self) -> bool {
+        self.stop();
+        self.game.current_best_move = Some(Arc::new(Mutex::new(None)));
+        self.game.stop_search = Arc::new(AtomicBool::new(false));
+        !self
+            .game
+            .board
+            .legal_moves(self.game.forced_capture)
+            .is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time_management::parse_go;
+    use crate::variant::Variant;
+    use cozy_chess::Color;
+
+    fn moves(moves: &[&str]) -> Vec<String> {
+        moves.iter().map(|uci_move| uci_move.to_string()).collect()
+    }
+
+    #[test]
+    fn test_search_returns_legal_move() {
+        let mut engine = Engine::new();
+        engine
+            .set_position(&PositionStart::StartPos, &moves(&["e2e4", "e7e5"]))
+            .unwrap();
+        let move_eval = engine.search(&parse_go(&["go", "depth", "2"])).unwrap();
+        assert!(engine.board().is_legal(move_eval.best_move));
+        assert_eq!(move_eval.pv.first(), Some(&move_eval.best_move));
+        assert_eq!(engine.best_move(), Some(move_eval));
+    }
+
+    #[test]
+    fn test_stop_ends_infinite_search() {
+        let mut engine = Engine::new();
+        assert!(engine.start_search(parse_go(&["go", "infinite"])));
+        let move_eval = engine.stop().unwrap();
+        assert!(engine.board().is_legal(move_eval.best_move));
+        assert!(!engine.is_searching());
+        assert_eq!(engine.stop(), None);
+    }
+
+    #[test]
+    fn test_finished_game_is_not_searched() {
+        let mut engine = Engine::new();
+        let mate = PositionStart::Fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1".to_string());
+        engine.set_position(&mate, &[]).unwrap();
+        assert_eq!(engine.search(&SearchLimits::default()), None);
+        assert!(!engine.start_search(SearchLimits::default()));
+    }
+
+    #[test]
+    fn test_interrupt_stops_search() {
+        let mut engine = Engine::new();
+        let mut calls = 0;
+        let move_eval = engine.search_interruptible(&parse_go(&["go", "infinite"]), &mut || {
+            calls += 1;
+            calls > 3
+        });
+        assert!(engine.board().is_legal(move_eval.unwrap().best_move));
+        assert_eq!(calls, 4);
+    }
+
+    #[test]
+    fn test_play_moves_to_the_end_of_the_game() {
+        let mut engine = Engine::new();
+        for uci_move in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            assert_eq!(engine.outcome(), None);
+            engine.play_move(uci_move).unwrap();
+        }
+        assert_eq!(engine.outcome(), Some(Outcome::Winner(Color::Black)));
+        assert!(engine.play_move("e1f2").is_err());
+        assert_eq!(engine.evaluate(), engine.board().evaluate());
+    }
+
+    #[test]
+    fn test_errors_keep_engine_state() {
+        let mut engine = Engine::new();
+        assert!(engine.set_option("Colour", Some("red")).is_err());
+        assert!(engine.set_option("UCI_Variant", Some("bughouse")).is_err());
+        engine.set_option("UCI_Variant", Some("antichess")).unwrap();
+        let result = engine.set_position(&PositionStart::StartPos, &moves(&["e2e5"]));
+        assert!(result.is_err());
+        assert_eq!(engine.board(), &VariantBoard::startpos(Variant::Antichess));
+        engine.new_game();
+        assert_eq!(engine.board(), &VariantBoard::startpos(Variant::Antichess));
+    }
+}
@@ -0,0 +1,230 @@
+use crate::game_runner::{
+    parse_limits, parse_number, parse_option, play_game, GameSettings, Player, Score, TimeControl,
+};
+use crate::pgn::result_token;
+use crate::selfplay::DEFAULT_MAX_PLIES;
+use crate::time_management::SearchLimits;
+use crate::uci_command::PositionStart;
+use crate::variant::Variant;
+use cozy_chess::Color;
+use std::fs::{self, File};
+use std::io::{self, Write};
+
+/// The settings of a match between Wheatley and an external UCI engine
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchConfig {
+    /// The command that starts the opponent
+    pub engine_command: String,
+    pub engine_args: Vec<String>,
+    /// UCI options of the opponent, as names and values
+    pub engine_options: Vec<(String, Option<String>)>,
+    pub engine_limits: SearchLimits,
+    /// UCI options of Wheatley, as names and values
+    pub options: Vec<(String, Option<String>)>,
+    pub limits: SearchLimits,
+    pub games: u32,
+    pub variant: Variant,
+    pub time_control: Option<TimeControl>,
+    /// The positions the games start from, each is played once with either colour
+    pub openings: Vec<PositionStart>,
+    /// Games still going after this many plies are drawn
+    pub max_plies: usize,
+    /// Where the games are written, or None to write them to standard output
+    pub pgn_path: Option<String>,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        MatchConfig {
+            engine_command: String::new(),
+            engine_args: Vec::new(),
+            engine_options: Vec::new(),
+            engine_limits: SearchLimits::default(),
+            options: Vec::new(),
+            limits: SearchLimits::default(),
+            games: 2,
+            variant: Variant::Standard,
+            time_control: None,
+            openings: vec![PositionStart::StartPos],
+            max_plies: DEFAULT_MAX_PLIES,
+            pgn_path: None,
+        }
+    }
+}
+
+/// Reads the arguments given after match:
+/// --engine COMMAND, --engine-arg ARG, --engine-option NAME=VALUE and --engine-go LIMITS
+/// for the opponent, --option NAME=VALUE and --go LIMITS for Wheatley, and
+/// --games N, --variant NAME, --tc BASE+INC, --openings FILE, --max-plies N and --pgn FILE.
+/// LIMITS are written as for the go command
+pub fn parse_args(args: &[String]) -> Result<MatchConfig, String> {
+    let mut config = MatchConfig::default();
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("{flag} requires a value"))
+        };
+        match flag.as_str() {
+            "--engine" => config.engine_command = value()?.clone(),
+            "--engine-arg" => config.engine_args.push(value()?.clone()),
+            "--engine-option" => config.engine_options.push(parse_option(value()?)),
+            "--engine-go" => config.engine_limits = parse_limits(value()?),
+            "--option" => config.options.push(parse_option(value()?)),
+            "--go" => config.limits = parse_limits(value()?),
+            "--games" => config.games = parse_number(flag, value()?)?,
+            "--variant" => config.variant = value()?.parse()?,
+            "--tc" => config.time_control = Some(value()?.parse()?),
+            "--openings" => config.openings = read_openings(value()?)?,
+            "--max-plies" => config.max_plies = parse_number(flag, value()?)?,
+            "--pgn" => config.pgn_path = Some(value()?.clone()),
+            _ => return Err(format!("Unknown argument [{flag}]")),
+        }
+    }
+    if config.engine_command.is_empty() {
+        return Err("The engine to play against must be given with --engine".to_string());
+    }
+    Ok(config)
+}
+
+/// Reads a file of openings with a FEN on every line.
+/// Blank lines and lines starting with # are skipped
+fn read_openings(path: &str) -> Result<Vec<PositionStart>, String> {
+    let text =
+        fs::read_to_string(path).map_err(|error| format!("Can not read [{path}]: {error}"))?;
+    let openings: Vec<PositionStart> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|fen| match fen {
+            "startpos" => PositionStart::StartPos,
+            fen => PositionStart::Fen(fen.to_string()),
+        })
+        .collect();
+    if openings.is_empty() {
+        return Err(format!("[{path}] holds no openings"));
+    }
+    Ok(openings)
+}
+
+/// Plays the match, writing every game as PGN, and returns the score of Wheatley.
+/// Every opening is played twice, with Wheatley taking white in the first game
+pub fn run(config: &MatchConfig) -> Result<Score, String> {
+    let mut wheatley = Player::wheatley(
+        "wheatleybot",
+        &config.options,
+        config.limits.clone(),
+        config.variant,
+    )?;
+    let mut opponent = Player::external(
+        &config.engine_command,
+        &config.engine_args,
+        &config.engine_options,
+        config.engine_limits.clone(),
+        config.variant,
+    )?;
+    let mut output: Box<dyn Write> = match &config.pgn_path {
+        Some(path) => Box::new(
+            File::create(path).map_err(|error| format!("Can not create [{path}]: {error}"))?,
+        ),
+        None => Box::new(io::stdout()),
+    };
+    let mut score = Score::default();
+    for round in 1..=config.games {
+        let opening = (round as usize - 1) / 2 % config.openings.len();
+        let settings = GameSettings {
+            event: format!("wheatleybot vs {}", opponent.name),
+            round,
+            variant: config.variant,
+            start: config.openings[opening].clone(),
+            time_control: config.time_control,
+            max_plies: config.max_plies,
+            opening_plies: 0,
+        };
+        let wheatley_color = match round % 2 {
+            1 => Color::White,
+            _ => Color::Black,
+        };
+        let (game, outcome) = match wheatley_color {
+            Color::White => play_game(&mut wheatley, &mut opponent, &settings)?,
+            Color::Black => play_game(&mut opponent, &mut wheatley, &settings)?,
+        };
+        score.add(outcome, wheatley_color);
+        writeln!(output, "{game}").map_err(|error| format!("Can not write the game: {error}"))?;
+        if config.pgn_path.is_some() {
+            println!(
+                "Game {round}: {} - {} {}",
+                game.tag("White").unwrap_or("?"),
+                game.tag("Black").unwrap_or("?"),
+                result_token(Some(outcome))
+            );
+        }
+    }
+    Ok(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uci_engine::tests::stub_args;
+    use std::env;
+
+    fn stub_config(on_go: &str, name: &str) -> MatchConfig {
+        MatchConfig {
+            engine_command: "sh".to_string(),
+            engine_args: stub_args(on_go),
+            limits: parse_limits("depth 1"),
+            pgn_path: Some(
+                env::temp_dir()
+                    .join(format!("{name}_{}.pgn", std::process::id()))
+                    .display()
+                    .to_string(),
+            ),
+            ..MatchConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args: Vec<String> = ["--engine", "stockfish", "--engine-option", "Threads=2"]
+            .iter()
+            .chain(&["--tc", "10+0.1", "--games", "6"])
+            .map(|arg| arg.to_string())
+            .collect();
+        let config = parse_args(&args).unwrap();
+        assert_eq!(config.engine_command, "stockfish");
+        assert_eq!(config.games, 6);
+        assert_eq!(config.time_control, Some("10+0.1".parse().unwrap()));
+        assert!(parse_args(&["--games".to_string(), "2".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_colours_alternate_over_openings() {
+        // The opponent always plays Ra8, which mates as white and is illegal as black
+        let mut config = stub_config("echo 'bestmove a1a8'", "wheatley_match");
+        config.openings = vec![PositionStart::Fen(
+            "7k/8/6K1/8/8/8/8/R7 w - - 0 1".to_string(),
+        )];
+        let score = run(&config).unwrap();
+        assert_eq!(score.losses, 1);
+        assert_eq!(score.wins + score.draws, 1);
+        let pgn_path = config.pgn_path.unwrap();
+        let pgn = fs::read_to_string(&pgn_path).unwrap();
+        fs::remove_file(pgn_path).unwrap();
+        assert!(pgn.contains("[White \"wheatleybot\"]\n[Black \"Stub\"]"));
+        assert!(pgn.contains("[White \"Stub\"]\n[Black \"wheatleybot\"]"));
+        assert!(pgn.contains("1. Ra8# 1-0"));
+    }
+
+    #[test]
+    fn test_silent_opponent_loses_on_time() {
+        let mut config = stub_config("true", "wheatley_match_time");
+        config.time_control = Some("0.2".parse().unwrap());
+        let score = run(&config).unwrap();
+        assert_eq!(score.wins, 2);
+        let pgn_path = config.pgn_path.unwrap();
+        let pgn = fs::read_to_string(&pgn_path).unwrap();
+        fs::remove_file(pgn_path).unwrap();
+        assert_eq!(pgn.matches("[Termination \"time forfeit\"]").count(), 2);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::predicted_eval::get_material_evaluation;
+use crate::variant::VariantBoard;
+use cozy_chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_rook_moves, Color, Move, Piece,
+};
+use std::fmt;
+use std::str::FromStr;
+
+/// How much every square a piece can move to is worth to the mobility evaluator
+const MOBILITY_WEIGHT: i32 = 5;
+/// The largest score the random evaluator gives, either way
+const RANDOM_RANGE: u64 = 100;
+
+/// Scores positions for the search
+pub trait Evaluator {
+    /// Returns the evaluation of the position for white in centipawns
+    fn evaluate(&mut self, board: &VariantBoard) -> i32;
+
+    /// Called before the search plays a move on the board, so that an evaluator
+    /// can update its state instead of looking at the whole board again
+    fn make_move(&mut self, _board: &VariantBoard, _played_move: Move) {}
+
+    /// Called after the search takes back the last move it played
+    fn unmake_move(&mut self) {}
+}
+
+/// Material and piece square tables, tapered by game phase.
+/// In antichess every piece is worth the same and their squares mean nothing
+pub struct PieceSquareEvaluator;
+
+impl Evaluator for PieceSquareEvaluator {
+    fn evaluate(&mut self, board: &VariantBoard) -> i32 {
+        board.evaluate()
+    }
+}
+
+/// Counts material and nothing else
+pub struct MaterialEvaluator;
+
+impl Evaluator for MaterialEvaluator {
+    fn evaluate(&mut self, board: &VariantBoard) -> i32 {
+        match board {
+            VariantBoard::Standard(board) => get_material_evaluation(board),
+            // Antichess boards only ever count material
+            VariantBoard::Antichess(_) | VariantBoard::Suicide(_) => board.evaluate(),
+        }
+    }
+}
+
+/// Scores every position with a number drawn from its hash, so the same position
+/// always gets the same score while the moves played look random
+pub struct RandomEvaluator;
+
+impl Evaluator for RandomEvaluator {
+    fn evaluate(&mut self, board: &VariantBoard) -> i32 {
+        // Mix the bits of the hash so that similar positions get unrelated scores
+        let mut mixed = board.hash().wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        mixed ^= mixed >> 32;
+        (mixed % (RANDOM_RANGE * 2 + 1)) as i32 - RANDOM_RANGE as i32
+    }
+}
+
+/// Material along with the number of squares each side's pieces can move to
+pub struct MobilityEvaluator;
+
+impl Evaluator for MobilityEvaluator {
+    fn evaluate(&mut self, board: &VariantBoard) -> i32 {
+        let material = MaterialEvaluator.evaluate(board);
+        material + (mobility(board, Color::White) - mobility(board, Color::Black)) * MOBILITY_WEIGHT
+    }
+}
+
+/// Counts the squares the pieces of the colour attack that are not occupied by
+/// their own pieces, whoever is to move. Pawns are left out
+fn mobility(board: &VariantBoard, color: Color) -> i32 {
+    let occupied = board.occupied();
+    let own_pieces = board.colors(color);
+    let mut squares = 0;
+    for piece in [
+        Piece::Knight,
+        Piece::Bishop,
+        Piece::Rook,
+        Piece::Queen,
+        Piece::King,
+    ] {
+        for square in board.colored_pieces(color, piece) {
+            let moves = match piece {
+                Piece::Knight => get_knight_moves(square),
+                Piece::Bishop => get_bishop_moves(square, occupied),
+                Piece::Rook => get_rook_moves(square, occupied),
+                Piece::Queen => {
+                    get_bishop_moves(square, occupied) | get_rook_moves(square, occupied)
+                }
+                _ => get_king_moves(square),
+            };
+            squares += (moves & !own_pieces).len() as i32;
+        }
+    }
+    squares
+}
+
+/// The evaluators that can be chosen with the Evaluator option
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvaluatorKind {
+    #[default]
+    PieceSquare,
+    Material,
+    Random,
+    Mobility,
+}
+
+impl EvaluatorKind {
+    pub const ALL: [EvaluatorKind; 4] = [
+        EvaluatorKind::PieceSquare,
+        EvaluatorKind::Material,
+        EvaluatorKind::Random,
+        EvaluatorKind::Mobility,
+    ];
+
+    /// Evaluates a single position with this kind of evaluator
+    pub fn evaluate(self, board: &VariantBoard) -> i32 {
+        match self {
+            EvaluatorKind::PieceSquare => PieceSquareEvaluator.evaluate(board),
+            EvaluatorKind::Material => MaterialEvaluator.evaluate(board),
+            EvaluatorKind::Random => RandomEvaluator.evaluate(board),
+            EvaluatorKind::Mobility => MobilityEvaluator.evaluate(board),
+        }
+    }
+}
+
+impl FromStr for EvaluatorKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<EvaluatorKind, String> {
+        EvaluatorKind::ALL
+            .into_iter()
+            .find(|kind| kind.to_string().eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("Unknown evaluator [{name}]"))
+    }
+}
+
+impl fmt::Display for EvaluatorKind {
+    /// Writes the name of the evaluator as used by the Evaluator option
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            EvaluatorKind::PieceSquare => "pst",
+            EvaluatorKind::Material => "material",
+            EvaluatorKind::Random => "random",
+            EvaluatorKind::Mobility => "mobility",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variant::Variant;
+
+    fn standard(fen: &str) -> VariantBoard {
+        VariantBoard::Standard(fen.parse().unwrap())
+    }
+
+    #[test]
+    fn test_names_round_trip() {
+        for kind in EvaluatorKind::ALL {
+            assert_eq!(kind.to_string().parse(), Ok(kind));
+        }
+        assert_eq!("Mobility".parse(), Ok(EvaluatorKind::Mobility));
+        assert!("neural".parse::<EvaluatorKind>().is_err());
+    }
+
+    #[test]
+    fn test_start_position_is_balanced() {
+        let board = VariantBoard::startpos(Variant::Standard);
+        for kind in [
+            EvaluatorKind::PieceSquare,
+            EvaluatorKind::Material,
+            EvaluatorKind::Mobility,
+        ] {
+            assert_eq!(kind.evaluate(&board), 0, "{kind}");
+        }
+    }
+
+    #[test]
+    fn test_material_ignores_squares() {
+        let centre = standard("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1");
+        let corner = standard("4k3/8/8/8/8/8/8/N3K3 w - - 0 1");
+        assert_eq!(MaterialEvaluator.evaluate(&centre), 250);
+        assert_eq!(MaterialEvaluator.evaluate(&corner), 250);
+        assert!(PieceSquareEvaluator.evaluate(&centre) > PieceSquareEvaluator.evaluate(&corner));
+    }
+
+    #[test]
+    fn test_mobility_prefers_active_pieces() {
+        let centre = standard("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1");
+        let corner = standard("4k3/8/8/8/8/8/8/N3K3 w - - 0 1");
+        // A knight on d4 reaches 8 squares, on a1 only 2
+        assert_eq!(
+            MobilityEvaluator.evaluate(&centre) - MobilityEvaluator.evaluate(&corner),
+            6 * MOBILITY_WEIGHT
+        );
+    }
+
+    #[test]
+    fn test_random_is_stable_and_bounded() {
+        let board = standard("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+        let score = RandomEvaluator.evaluate(&board);
+        assert_eq!(RandomEvaluator.evaluate(&board), score);
+        let scores: Vec<i32> = board
+            .legal_moves(false)
+            .into_iter()
+            .map(|played_move| {
+                let mut child = board.clone();
+                child.play(played_move);
+                RandomEvaluator.evaluate(&child)
+            })
+            .collect();
+        assert!(scores
+            .iter()
+            .all(|score| score.abs() <= RANDOM_RANGE as i32));
+        assert!(scores.iter().any(|other| *other != scores[0]));
+    }
+}
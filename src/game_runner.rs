@@ -0,0 +1,394 @@
+use crate::engine_api::Engine;
+use crate::handle_uci_output::uci_move;
+use crate::pgn::{eval_comment, PgnGame};
+use crate::play::MoveEval;
+use crate::time_management::{parse_go, SearchLimits};
+use crate::uci_command::PositionStart;
+use crate::uci_engine::{UciEngine, UciEngineError};
+use crate::variant::{Outcome, Variant};
+use cozy_chess::Color;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// How far over its clock a player may go before it loses on time,
+/// to allow for the time it takes to send a move
+const TIME_MARGIN: Duration = Duration::from_millis(100);
+
+/// Who makes the moves of a player
+pub enum PlayerKind {
+    Wheatley(Engine),
+    /// A UCI engine running as another process
+    External(UciEngine),
+}
+
+/// One side of the games of a match
+pub struct Player {
+    pub name: String,
+    pub kind: PlayerKind,
+    /// The limits of every search, the clock is added to them when games are timed
+    pub limits: SearchLimits,
+}
+
+/// The reasons a player loses a game before it is decided on the board
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlayerError {
+    OutOfTime,
+    /// The player sent an illegal move or stopped answering
+    Failed(String),
+}
+
+impl Player {
+    /// Creates a Wheatley player with the UCI options set
+    pub fn wheatley(
+        name: &str,
+        options: &[(String, Option<String>)],
+        limits: SearchLimits,
+        variant: Variant,
+    ) -> Result<Player, String> {
+        let mut engine = Engine::new();
+        engine.set_option("UCI_Variant", Some(&variant.to_string()))?;
+        for (name, value) in options {
+            engine.set_option(name, value.as_deref())?;
+        }
+        Ok(Player {
+            name: name.to_string(),
+            kind: PlayerKind::Wheatley(engine),
+            limits,
+        })
+    }
+
+    /// Starts an external engine and sets its UCI options
+    pub fn external(
+        command: &str,
+        args: &[String],
+        options: &[(String, Option<String>)],
+        limits: SearchLimits,
+        variant: Variant,
+    ) -> Result<Player, String> {
+        let mut engine = UciEngine::start(command, args).map_err(|error| error.to_string())?;
+        if variant != Variant::Standard {
+            engine
+                .set_option("UCI_Variant", Some(&variant.to_string()))
+                .map_err(|error| error.to_string())?;
+        }
+        for (name, value) in options {
+            engine
+                .set_option(name, value.as_deref())
+                .map_err(|error| error.to_string())?;
+        }
+        Ok(Player {
+            name: engine.name().to_string(),
+            kind: PlayerKind::External(engine),
+            limits,
+        })
+    }
+
+    fn new_game(&mut self) -> Result<(), String> {
+        match &mut self.kind {
+            PlayerKind::Wheatley(engine) => {
+                engine.new_game();
+                Ok(())
+            }
+            PlayerKind::External(engine) => engine.new_game().map_err(|error| error.to_string()),
+        }
+    }
+
+    /// Returns the move the player plays after the moves in UCI notation,
+    /// along with the result of the search if the player is Wheatley
+    fn choose_move(
+        &mut self,
+        start: &PositionStart,
+        moves: &[String],
+        limits: &SearchLimits,
+        timeout: Option<Duration>,
+    ) -> Result<(String, Option<MoveEval>), PlayerError> {
+        match &mut self.kind {
+            PlayerKind::Wheatley(engine) => {
+                engine
+                    .set_position(start, moves)
+                    .map_err(PlayerError::Failed)?;
+                let move_eval = engine
+                    .search(limits)
+                    .ok_or_else(|| PlayerError::Failed("No move was found".to_string()))?;
+                let chosen_move = uci_move(engine.board(), move_eval.best_move, engine.chess960());
+                Ok((chosen_move, Some(move_eval)))
+            }
+            PlayerKind::External(engine) => engine
+                .search(start, moves, limits, timeout)
+                .map(|chosen_move| (chosen_move, None))
+                .map_err(|error| match error {
+                    UciEngineError::Timeout => PlayerError::OutOfTime,
+                    error => PlayerError::Failed(error.to_string()),
+                }),
+        }
+    }
+}
+
+/// A clock that starts at base and gains increment after every move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+impl FromStr for TimeControl {
+    type Err = String;
+
+    /// Reads BASE+INCREMENT or BASE, both in seconds
+    fn from_str(text: &str) -> Result<TimeControl, String> {
+        let (base, increment) = text.split_once('+').unwrap_or((text, "0"));
+        let seconds = |value: &str| {
+            value
+                .parse()
+                .ok()
+                .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                .ok_or_else(|| format!("Invalid time control [{text}]"))
+        };
+        Ok(TimeControl {
+            base: seconds(base)?,
+            increment: seconds(increment)?,
+        })
+    }
+}
+
+impl fmt::Display for TimeControl {
+    /// Writes the time control as the PGN TimeControl tag does, in seconds
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let base = self.base.as_secs_f64();
+        match self.increment.is_zero() {
+            true => write!(f, "{base}"),
+            false => write!(f, "{base}+{}", self.increment.as_secs_f64()),
+        }
+    }
+}
+
+/// How a single game is played
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameSettings {
+    pub event: String,
+    pub round: u32,
+    pub variant: Variant,
+    pub start: PositionStart,
+    /// The clock of both players, or None if only the limits of the players count
+    pub time_control: Option<TimeControl>,
+    /// Games still going after this many plies are drawn
+    pub max_plies: usize,
+    /// The number of random plies played before the players take over, so that
+    /// games between players that search the same way are not all the same
+    pub opening_plies: usize,
+}
+
+/// The results of a match from the point of view of one player
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Score {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Score {
+    /// Counts the outcome of a game the player played with the colour
+    pub fn add(&mut self, outcome: Outcome, color: Color) {
+        match outcome {
+            Outcome::Winner(winner) if winner == color => self.wins += 1,
+            Outcome::Winner(_) => self.losses += 1,
+            Outcome::Draw => self.draws += 1,
+        }
+    }
+}
+
+/// Plays one game until it is decided by the rules, a player forfeits
+/// or it is adjudicated a draw. Fails if the game could not be set up
+pub fn play_game(
+    white: &mut Player,
+    black: &mut Player,
+    settings: &GameSettings,
+) -> Result<(PgnGame, Outcome), String> {
+    let mut referee = Engine::new();
+    referee.set_option("UCI_Variant", Some(&settings.variant.to_string()))?;
+    referee.set_position(&settings.start, &[])?;
+    let mut game = PgnGame::new(referee.board().clone());
+    game.set_tag("Event", &settings.event);
+    game.set_tag("Round", &settings.round.to_string());
+    game.set_tag("White", &white.name);
+    game.set_tag("Black", &black.name);
+    if let Some(time_control) = settings.time_control {
+        game.set_tag("TimeControl", &time_control.to_string());
+    }
+    white.new_game()?;
+    black.new_game()?;
+
+    let mut clocks = settings
+        .time_control
+        .map(|time_control| [time_control.base; 2]);
+    let mut random = Random::new(settings.round as u64);
+    let mut uci_moves: Vec<String> = Vec::new();
+    let (outcome, termination) = loop {
+        if let Some(outcome) = referee.outcome() {
+            break (outcome, "normal");
+        }
+        if game.moves.len() >= settings.max_plies {
+            break (Outcome::Draw, "adjudication");
+        }
+        let side_to_move = referee.board().side_to_move();
+        let (uci_move, move_eval) = if game.moves.len() < settings.opening_plies {
+            let board = referee.board();
+            let moves = board.legal_moves(false);
+            (
+                uci_move(board, moves[random.below(moves.len())], false),
+                None,
+            )
+        } else {
+            let player = match side_to_move {
+                Color::White => &mut *white,
+                Color::Black => &mut *black,
+            };
+            let mut limits = player.limits.clone();
+            let mut timeout = None;
+            if let (Some(clocks), Some(time_control)) = (clocks, settings.time_control) {
+                limits.white_time = Some(clocks[Color::White as usize]);
+                limits.black_time = Some(clocks[Color::Black as usize]);
+                limits.white_increment = Some(time_control.increment);
+                limits.black_increment = Some(time_control.increment);
+                timeout = Some(clocks[side_to_move as usize] + TIME_MARGIN);
+            }
+            let start_time = Instant::now();
+            let chosen_move = player.choose_move(&settings.start, &uci_moves, &limits, timeout);
+            let elapsed = start_time.elapsed();
+            let chosen_move = match chosen_move {
+                Ok(chosen_move) => chosen_move,
+                Err(PlayerError::OutOfTime) => {
+                    break (Outcome::Winner(!side_to_move), "time forfeit")
+                }
+                Err(PlayerError::Failed(reason)) => {
+                    eprintln!(
+                        "{} forfeits round {}: {reason}",
+                        player.name, settings.round
+                    );
+                    break (Outcome::Winner(!side_to_move), "rules infraction");
+                }
+            };
+            if let (Some(clocks), Some(time_control)) = (&mut clocks, settings.time_control) {
+                let clock = &mut clocks[side_to_move as usize];
+                if elapsed > *clock + TIME_MARGIN {
+                    break (Outcome::Winner(!side_to_move), "time forfeit");
+                }
+                *clock = clock.saturating_sub(elapsed) + time_control.increment;
+            }
+            chosen_move
+        };
+        match referee.play_move(&uci_move) {
+            Ok(played_move) => {
+                uci_moves.push(uci_move);
+                game.push_move(played_move, move_eval.as_ref().map(eval_comment));
+            }
+            Err(reason) => {
+                let name = match side_to_move {
+                    Color::White => &white.name,
+                    Color::Black => &black.name,
+                };
+                eprintln!("{name} forfeits round {}: {reason}", settings.round);
+                break (Outcome::Winner(!side_to_move), "rules infraction");
+            }
+        }
+    };
+    game.set_result(Some(outcome));
+    game.set_tag("Termination", termination);
+    Ok((game, outcome))
+}
+
+/// Splits NAME=VALUE, an option without a value is a button
+pub fn parse_option(option: &str) -> (String, Option<String>) {
+    match option.split_once('=') {
+        Some((name, value)) => (name.trim().to_string(), Some(value.trim().to_string())),
+        None => (option.trim().to_string(), None),
+    }
+}
+
+/// Reads search limits written as the arguments of the go command
+pub fn parse_limits(limits: &str) -> SearchLimits {
+    let tokens: Vec<&str> = std::iter::once("go")
+        .chain(limits.split_whitespace())
+        .collect();
+    parse_go(&tokens)
+}
+
+/// Reads a number given on the command line for the flag
+pub fn parse_number<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value [{value}] for {flag}"))
+}
+
+/// A small xorshift generator for choosing opening moves, seeded so that a
+/// match can be played again with the same openings
+struct Random(u64);
+
+impl Random {
+    fn new(seed: u64) -> Random {
+        // The state of xorshift must never be zero
+        Random(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    /// Returns a number below the bound
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> GameSettings {
+        GameSettings {
+            event: "test".to_string(),
+            round: 1,
+            variant: Variant::Standard,
+            start: PositionStart::StartPos,
+            time_control: None,
+            max_plies: 6,
+            opening_plies: 0,
+        }
+    }
+
+    fn wheatley(name: &str) -> Player {
+        Player::wheatley(name, &[], parse_limits("depth 1"), Variant::Standard).unwrap()
+    }
+
+    #[test]
+    fn test_time_control() {
+        let time_control: TimeControl = "10+0.1".parse().unwrap();
+        assert_eq!(time_control.base, Duration::from_secs(10));
+        assert_eq!(time_control.increment, Duration::from_millis(100));
+        assert_eq!(time_control.to_string(), "10+0.1");
+        assert_eq!("60".parse::<TimeControl>().unwrap().to_string(), "60");
+        assert!("fast".parse::<TimeControl>().is_err());
+    }
+
+    #[test]
+    fn test_long_games_are_adjudicated() {
+        let (game, outcome) =
+            play_game(&mut wheatley("A"), &mut wheatley("B"), &settings()).unwrap();
+        assert_eq!(outcome, Outcome::Draw);
+        assert_eq!(game.moves.len(), 6);
+        assert_eq!(game.tag("Termination"), Some("adjudication"));
+    }
+
+    #[test]
+    fn test_timed_games_are_played() {
+        let settings = GameSettings {
+            time_control: Some("1+0.01".parse().unwrap()),
+            ..settings()
+        };
+        let mut white = wheatley("A");
+        white.limits = SearchLimits::default();
+        let (game, _) = play_game(&mut white, &mut wheatley("B"), &settings).unwrap();
+        assert_eq!(game.tag("TimeControl"), Some("1+0.01"));
+        assert_eq!(game.moves.len(), 6);
+    }
+}
@@ -1,18 +1,26 @@
-use cozy_chess::{Board, Move};
+use cozy_chess::{File, Move, Piece, Square};
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::{self};
 
-use crate::engine::get_move;
+use crate::antichess;
+use crate::bench;
+use crate::chess960;
+use crate::engine::{get_move, DEFAULT_SEARCH_DEPTH, MAX_SKILL};
+use crate::evaluator::EvaluatorKind;
 use crate::handle_uci_output;
-use crate::play::Game;
+use crate::perft;
+use crate::play::{Game, Protocol};
+use crate::predicted_eval;
+use crate::time_management::SearchLimits;
+use crate::transposition_table::TranspositionTable;
+use crate::uci_command::PositionStart;
+use crate::uci_options;
+use crate::variant::{Variant, VariantBoard};
 
-pub fn debug(game: Game) -> Game {
-    Game {
-        debug_mode: true,
-        ..game
-    }
+pub fn debug(debug_mode: bool, game: Game) -> Game {
+    Game { debug_mode, ..game }
 }
 
 pub fn is_ready(game: Game) -> Game {
@@ -20,52 +28,132 @@ pub fn is_ready(game: Game) -> Game {
     game
 }
 
-pub fn set_option(input_tokens: &Vec<&str>, game: Game) -> Game {
-    if input_tokens[1] != "name" {
-        handle_uci_output::send_info("Invalid option sent, name not present");
-        panic!("Invalid UCI Command");
-    }
-    if input_tokens[2] != "ForcedCapture" {
-        handle_uci_output::send_info("Invalid option sent, id not reckonised");
-        panic!("Invalid UCI Command");
+pub fn set_option(name: &str, value: Option<&str>, game: Game) -> Game {
+    let game = stop(game);
+    match apply_option(name, value, &game) {
+        Ok(game) => game,
+        Err(error) => {
+            handle_uci_output::info_string(&error);
+            game
+        }
     }
-    let forced_capture = input_tokens[4].parse().unwrap();
-    Game {
-        forced_capture: forced_capture,
-        ..game
+}
+
+/// Returns the game with the option set, or the reason the option could not be set
+pub fn apply_option(name: &str, value: Option<&str>, game: &Game) -> Result<Game, String> {
+    uci_options::apply(name, value, game.clone())
+}
+
+pub fn position(start: &PositionStart, moves: &[String], game: Game) -> Game {
+    let game = stop(game);
+    let game = match set_position(start, moves, &game) {
+        Ok(game) => game,
+        Err(error) => {
+            handle_uci_output::info_string(&error);
+            return game;
+        }
+    };
+    if game.debug_mode {
+        let board = &game.board;
+        let fen = match game.chess960 {
+            true => format!("{board:#}"),
+            false => board.to_string(),
+        };
+        handle_uci_output::info_string(&format!("Position set to {fen}"));
+        let repetitions = game.previous_boards[&board.repetition_key()];
+        if let Some(reason) = board.draw_reason(repetitions) {
+            let claim = match reason.is_claimable() {
+                true => "may be claimed as",
+                false => "is",
+            };
+            handle_uci_output::info_string(&format!("Position {claim} drawn by {reason:?}"));
+        }
     }
+    game
 }
 
-pub fn position(input_tokens: &Vec<&str>, game: Game) -> Game {
+/// Returns the game with the board set to the position after the moves,
+/// or the reason the position could not be set up
+pub fn set_position(start: &PositionStart, moves: &[String], game: &Game) -> Result<Game, String> {
     let mut previous_boards: HashMap<u64, i32> = HashMap::new();
-    // First token is the position command
-    if input_tokens[1] != "startpos" {
-        handle_uci_output::send_info("Requires a starting point of startpos");
-        panic!("Invalid UCI Command")
-    }
-    // if input_tokens[2] != "moves" {
-    //     handle_uci_output::send_info("After startpos requires moves");
-    //     panic!("Invalid UCI Command")
-    // }
-    let mut board = Board::startpos();
-    previous_boards.insert(board.hash(), 1);
-    for i in 3..input_tokens.len() {
-        let played_move = input_tokens[i].parse::<Move>().unwrap();
-        // move_history.push(played_move);
+    let mut board = match start {
+        PositionStart::StartPos => VariantBoard::startpos(game.variant),
+        PositionStart::Fen(fen) => match game.variant {
+            Variant::Standard if game.chess960 => chess960::from_fen(fen)
+                .map(VariantBoard::Standard)
+                .map_err(|error| error.to_string()),
+            _ => VariantBoard::from_fen(fen, game.variant),
+        }
+        .map_err(|error| format!("Invalid FEN [{fen}]: {error}"))?,
+    };
+    previous_boards.insert(board.repetition_key(), 1);
+    for uci_move in moves {
+        let played_move = parse_uci_move(&board, uci_move, game.chess960)
+            .ok_or_else(|| format!("Illegal move [{uci_move}] in position {board}"))?;
         board.play(played_move);
-        let board_hash = board.hash();
         previous_boards
-            .entry(board_hash)
-            .and_modify(|board_hash| *board_hash += 1)
+            .entry(board.repetition_key())
+            .and_modify(|count| *count += 1)
             .or_insert(1);
     }
-    Game {
-        board: board,
-        ..game
+    Ok(Game {
+        board,
+        previous_boards,
+        ..game.clone()
+    })
+}
+
+/// Converts a move in UCI notation into a legal move on the board.
+/// UCI sends castling as the king moving two squares, while cozy-chess
+/// represents castling as the king capturing its own rook.
+/// Chess960 castling is already sent as the king capturing its own rook.
+/// Antichess has no castling, but pawns may promote to a king
+pub fn parse_uci_move(board: &VariantBoard, uci_move: &str, chess960: bool) -> Option<Move> {
+    let played_move = match board {
+        VariantBoard::Standard(board) => {
+            let mut played_move = uci_move.parse::<Move>().ok()?;
+            if !chess960 {
+                convert_castling(board, &mut played_move);
+            }
+            played_move
+        }
+        VariantBoard::Antichess(_) | VariantBoard::Suicide(_) => antichess::parse_move(uci_move)?,
+    };
+    if board.is_legal(played_move) {
+        Some(played_move)
+    } else {
+        None
+    }
+}
+
+/// Replaces a king moving two squares towards a rook it may castle with by the king capturing that rook
+fn convert_castling(board: &cozy_chess::Board, played_move: &mut Move) {
+    let side_to_move = board.side_to_move();
+    if board
+        .colored_pieces(side_to_move, Piece::King)
+        .has(played_move.from)
+        && played_move.from.rank() == played_move.to.rank()
+    {
+        let castle_rights = board.castle_rights(side_to_move);
+        let rook_file = match (played_move.from.file(), played_move.to.file()) {
+            (File::E, File::G) => castle_rights.short,
+            (File::E, File::C) => castle_rights.long,
+            _ => None,
+        };
+        if let Some(rook_file) = rook_file {
+            played_move.to = Square::new(rook_file, played_move.from.rank());
+        }
     }
 }
 
-pub fn go(_input_tokens: &Vec<&str>, game: Game) -> Game {
+pub fn go(limits: SearchLimits, game: Game) -> Game {
+    let game = stop(game);
+    // Mated and stalemated positions have no move to search, the GUI is still answered
+    if game.board.legal_moves(game.forced_capture).is_empty() {
+        handle_uci_output::info_string("The side to move has no legal moves");
+        handle_uci_output::null_best_move();
+        return game;
+    }
     let changed_game = Game {
         is_searching: true,
         current_best_move: Option::Some(Arc::new(Mutex::new(Option::None))),
@@ -73,29 +161,77 @@ pub fn go(_input_tokens: &Vec<&str>, game: Game) -> Game {
         ..game
     };
     let new_game = changed_game.clone();
-    let _thread = thread::spawn(move || {
-        let even_newer_game = get_move(new_game);
-        let stopped = even_newer_game.stop_search.load(Ordering::SeqCst);
+    let search_thread = thread::spawn(move || {
+        let even_newer_game = get_move(new_game, &limits);
+        if limits.infinite {
+            // The best move is only sent once the GUI asks for it with stop
+            return;
+        }
+        // Whoever sets the stop flag first is responsible for sending the best move
+        let stopped = even_newer_game.stop_search.swap(true, Ordering::SeqCst);
         if !stopped {
             let older_binding = even_newer_game.current_best_move.unwrap();
             let binding = older_binding.lock().unwrap();
             let move_evaluation = binding.as_ref().unwrap();
-            handle_uci_output::best_move(&move_evaluation.best_move)
+            handle_uci_output::best_move(
+                &even_newer_game.board,
+                move_evaluation.best_move,
+                even_newer_game.chess960,
+            )
         }
     });
-    changed_game
+    Game {
+        search_thread: Arc::new(Mutex::new(Some(search_thread))),
+        ..changed_game
+    }
 }
 
-pub fn stop(game: Game) -> Game {
-    game.stop_search.store(true, Ordering::SeqCst);
-    // Note: To Avoid panics with valid UCI commands, the worker thread must maintain
-    // The lock on current best move untill it has written data into the field
-    let older_binding = game.current_best_move.unwrap();
-    let binding = older_binding.lock().unwrap();
-    let move_evaluation = binding.as_ref().unwrap();
+/// Counts the positions below every legal move, for checking the move generation
+pub fn perft(depth: u32, game: Game) -> Game {
+    let counts = perft::divide(&game.board, depth, game.forced_capture);
+    handle_uci_output::divide(&game.board, &counts, game.chess960);
+    game
+}
+
+/// Searches the bench positions and reports the node count and speed
+pub fn bench(depth: i32, game: Game) -> Game {
+    match bench::run(depth) {
+        Ok(result) => handle_uci_output::bench(&result),
+        Err(error) => handle_uci_output::info_string(&error),
+    }
+    game
+}
+
+/// Explains the static evaluation of the current position term by term
+pub fn eval(game: Game) -> Game {
+    let trace = match &game.board {
+        VariantBoard::Standard(board) => Some(predicted_eval::trace(board)),
+        // Antichess positions are scored by counting pieces, which needs no explaining
+        VariantBoard::Antichess(_) | VariantBoard::Suicide(_) => None,
+    };
+    let evaluation = game.evaluator.evaluate(&game.board);
+    handle_uci_output::eval(trace.as_ref(), evaluation, game.evaluator);
+    game
+}
 
-    // let move_evaluation = game.current_best_move.unwrap().lock().unwrap().unwrap();
-    handle_uci_output::best_move(&move_evaluation.best_move);
+/// Stops the search, if there is one, and waits for it to finish.
+/// The search holds the transposition table while it runs, so every command that
+/// changes the table or the game stops the search first
+pub fn stop(game: Game) -> Game {
+    if !game.is_searching {
+        return game;
+    }
+    // Whoever sets the stop flag first is responsible for sending the best move
+    let finished = game.stop_search.swap(true, Ordering::SeqCst);
+    if let Some(search_thread) = game.search_thread.lock().unwrap().take() {
+        search_thread.join().unwrap();
+    }
+    if !finished {
+        let older_binding = game.current_best_move.as_ref().unwrap();
+        let binding = older_binding.lock().unwrap();
+        let move_evaluation = binding.as_ref().unwrap();
+        handle_uci_output::best_move(&game.board, move_evaluation.best_move, game.chess960);
+    }
 
     Game {
         current_best_move: Option::None,
@@ -104,8 +240,19 @@ pub fn stop(game: Game) -> Game {
     }
 }
 
-pub fn uci_new_game() -> Game {
-    default_game()
+/// Starts a new game, keeping the options the GUI has set
+pub fn uci_new_game(game: Game) -> Game {
+    let game = stop(game);
+    game.transposition_table.lock().unwrap().clear();
+    Game {
+        board: VariantBoard::startpos(game.variant),
+        previous_boards: HashMap::new(),
+        is_searching: false,
+        current_best_move: Option::None,
+        stop_search: Arc::new(AtomicBool::new(false)),
+        nodes: 0,
+        ..game
+    }
 }
 
 pub fn register(game: Game) -> Game {
@@ -119,11 +266,179 @@ pub fn ponderhit(game: Game) -> Game {
 pub fn default_game() -> Game {
     Game {
         debug_mode: false,
-        board: Board::startpos(),
+        protocol: Protocol::Uci,
+        variant: Variant::Standard,
+        chess960: false,
+        board: VariantBoard::startpos(Variant::Standard),
         forced_capture: true,
+        depth: DEFAULT_SEARCH_DEPTH,
+        skill: MAX_SKILL,
+        evaluator: EvaluatorKind::default(),
         previous_boards: HashMap::new(),
         is_searching: false,
         current_best_move: Option::None,
         stop_search: Arc::new(AtomicBool::new(false)),
+        search_thread: Arc::new(Mutex::new(None)),
+        transposition_table: Arc::new(Mutex::new(TranspositionTable::default())),
+        nodes: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uci_command::{parse, UciCommand};
+    use cozy_chess::Board;
+
+    fn standard(fen: &str) -> VariantBoard {
+        VariantBoard::Standard(fen.parse().unwrap())
+    }
+
+    /// Parses and runs a command that changes the game without searching
+    fn run(command: &str, game: Game) -> Game {
+        match parse(command).unwrap() {
+            UciCommand::Debug(debug_mode) => debug(debug_mode, game),
+            UciCommand::SetOption { name, value } => set_option(&name, value.as_deref(), game),
+            UciCommand::UciNewGame => uci_new_game(game),
+            UciCommand::Position { start, moves } => position(&start, &moves, game),
+            UciCommand::Go(limits) => go(limits, game),
+            UciCommand::Stop => stop(game),
+            command => panic!("{command:?} is not run by the tests"),
+        }
+    }
+
+    #[test]
+    fn test_position_startpos_with_moves() {
+        let game = run("position startpos moves e2e4 e7e5", default_game());
+        let expected = standard("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+        assert_eq!(game.board, expected);
+    }
+
+    #[test]
+    fn test_position_fen_with_castling() {
+        let game = run(
+            "position fen r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1 moves e1g1 e8c8",
+            default_game(),
+        );
+        let expected = standard("2kr3r/8/8/8/8/8/8/R4RK1 w - - 2 2");
+        assert_eq!(game.board, expected);
+    }
+
+    #[test]
+    fn test_position_fen_without_clocks() {
+        let game = run("position fen 4k3/8/8/8/8/8/8/4K3 b - -", default_game());
+        assert_eq!(game.board.side_to_move(), cozy_chess::Color::Black);
+    }
+
+    #[test]
+    fn test_position_records_repetitions() {
+        let game = run(
+            "position startpos moves g1f3 g8f6 f3g1 f6g8",
+            default_game(),
+        );
+        let key = VariantBoard::Standard(Board::startpos()).repetition_key();
+        assert_eq!(game.previous_boards[&key], 2);
+        assert_eq!(game.previous_boards.len(), 4);
+    }
+
+    #[test]
+    fn test_position_errors_keep_previous_board() {
+        let game = run("position fen 4k3/8/8/8/8/8/8/4K3 w - - 0 1", default_game());
+        let invalid_fen = run("position fen not a fen", game.clone());
+        assert_eq!(invalid_fen.board, game.board);
+        let illegal_move = run("position startpos moves e2e4 e2e5", game.clone());
+        assert_eq!(illegal_move.board, game.board);
+        let malformed_move = run("position startpos moves e2", game.clone());
+        assert_eq!(malformed_move.board, game.board);
+    }
+
+    #[test]
+    fn test_invalid_options_are_ignored() {
+        let game = run("setoption name Hash value lots", default_game());
+        let game = run("setoption name ForcedCapture", game);
+        assert!(game.forced_capture);
+        let game = run("setoption name Ponder value true", game);
+        let game = run("setoption name ForcedCapture value false", game);
+        assert!(!game.forced_capture);
+        let game = run("debug on", game);
+        assert!(game.debug_mode);
+        assert!(!run("debug off", game).debug_mode);
+    }
+
+    #[test]
+    fn test_commands_stop_a_running_search() {
+        for command in [
+            "setoption name Clear Hash",
+            "setoption name Evaluator value material",
+            "setoption name UCI_Variant value antichess",
+            "ucinewgame",
+            "position startpos moves e2e4",
+            "go depth 1",
+        ] {
+            let game = run("go infinite", default_game());
+            let game = run(command, game);
+            let game = run("stop", game);
+            assert!(!game.is_searching, "{command}");
+            assert!(game.search_thread.lock().unwrap().is_none(), "{command}");
+        }
+    }
+
+    #[test]
+    fn test_go_without_legal_moves() {
+        for fen in [
+            "7k/6Q1/6K1/8/8/8/8/8 b - - 0 1",
+            "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+        ] {
+            let game = run(&format!("position fen {fen}"), default_game());
+            let game = run("go depth 2", game);
+            assert!(!game.is_searching, "{fen}");
+            assert!(game.current_best_move.is_none(), "{fen}");
+            // There is no search to stop, and the engine keeps answering
+            let game = run("stop", game);
+            let game = run("position startpos", game);
+            let game = run("go depth 1", game);
+            assert!(!run("stop", game).is_searching);
+        }
+    }
+
+    #[test]
+    fn test_new_game_keeps_options_and_table() {
+        let game = run("setoption name Depth value 5", default_game());
+        let game = run("position startpos moves e2e4", game);
+        let new_game = run("ucinewgame", game.clone());
+        assert_eq!(new_game.board, VariantBoard::startpos(Variant::Standard));
+        assert!(new_game.previous_boards.is_empty());
+        assert_eq!(new_game.depth, 5);
+        assert!(Arc::ptr_eq(
+            &new_game.transposition_table,
+            &game.transposition_table
+        ));
+    }
+
+    #[test]
+    fn test_set_variant() {
+        let game = run("setoption name UCI_Variant value antichess", default_game());
+        assert_eq!(game.variant, Variant::Antichess);
+        // Castling does not exist, so the king walks onto its rook's square only if it is empty
+        let game = run("position fen 4k3/8/8/8/8/8/8/4K2R w K - moves e1f1", game);
+        assert_eq!(game.board.to_string(), "4k3/8/8/8/8/8/8/5K1R b - - 1 1");
+        let game = run("ucinewgame", game);
+        assert_eq!(game.board, VariantBoard::startpos(Variant::Antichess));
+        let game = run("setoption name UCI_Variant value crazyhouse", game);
+        assert_eq!(game.variant, Variant::Antichess);
+        let game = run("setoption name UCI_Variant value chess", game);
+        assert_eq!(game.board, Board::startpos().into());
+    }
+
+    #[test]
+    fn test_position_chess960() {
+        let game = run("setoption name UCI_Chess960 value true", default_game());
+        let game = run(
+            "position fen nrkbbqrn/pppppppp/8/8/8/8/PPPPPPPP/NRK1BQRN w KQkq - moves c1b1",
+            game,
+        );
+        // The king castled long by capturing the rook on b1
+        let expected = standard("nrkbbqrn/pppppppp/8/8/8/8/PPPPPPPP/N1KRBQRN b gb - 1 1");
+        assert_eq!(game.board, expected);
     }
 }
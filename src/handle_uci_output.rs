@@ -1,4 +1,11 @@
-use cozy_chess::Move;
+use crate::bench::BenchResult;
+use crate::engine::win_distance;
+use crate::evaluator::EvaluatorKind;
+use crate::predicted_eval::{EvalTrace, MAX_PHASE};
+use crate::uci_options;
+use crate::variant::VariantBoard;
+use cozy_chess::{Color, File, Move, Piece, Square};
+use std::time::Duration;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -11,7 +18,9 @@ pub fn id() {
 }
 
 fn get_options() {
-    println!("option name ForceCapture type check default true");
+    for option in uci_options::options() {
+        println!("{option}");
+    }
 }
 
 /// Responds to the isready command
@@ -24,6 +33,173 @@ pub fn send_info(info: &str) {
     println!("info {info}");
 }
 
-pub fn best_move(requested_move: &Move) {
-    println!("bestmove {requested_move}");
+/// Sends a message to the GUI that it displays without interpreting
+pub fn info_string(message: &str) {
+    send_info(&format!("string {message}"));
+}
+
+/// Sends the move chosen in the position
+pub fn best_move(board: &VariantBoard, requested_move: Move, chess960: bool) {
+    println!("bestmove {}", uci_move(board, requested_move, chess960));
+}
+
+/// Sends the null move, as there is no move to play in a finished game
+pub fn null_best_move() {
+    println!("bestmove 0000");
+}
+
+/// Sends the number of positions below every move, then the total, in the form
+/// other engines use so that the output can be compared line by line
+pub fn divide(board: &VariantBoard, counts: &[(Move, u64)], chess960: bool) {
+    for (played_move, count) in counts {
+        println!("{}: {count}", uci_move(board, *played_move, chess960));
+    }
+    let total: u64 = counts.iter().map(|(_, count)| count).sum();
+    println!();
+    println!("Nodes searched: {total}");
+}
+
+/// Sends the nodes searched in every bench position, then the total and the speed.
+/// The total is the signature of the search, which only changes when the search does
+pub fn bench(result: &BenchResult) {
+    for (index, nodes) in result.nodes.iter().enumerate() {
+        println!("Position {}: {nodes} nodes", index + 1);
+    }
+    println!();
+    println!("Total time (ms): {}", result.elapsed.as_millis());
+    println!("Nodes searched: {}", result.total_nodes());
+    println!("Nodes/second: {}", result.nps());
+}
+
+/// Sends the breakdown of the static evaluation, if there is one, and the evaluation
+/// the search would use. Every value is in centipawns, and the totals are for white
+pub fn eval(trace: Option<&EvalTrace>, evaluation: i32, evaluator: EvaluatorKind) {
+    if let Some(trace) = trace {
+        println!(
+            "{:<16}|{:>9}{:>9} |{:>9}{:>9} |{:>9}",
+            "Term", "White MG", "White EG", "Black MG", "Black EG", "Total"
+        );
+        println!("{}", "-".repeat(16 + 20 + 20 + 10));
+        for term in &trace.terms {
+            println!(
+                "{:<16}|{:>9}{:>9} |{:>9}{:>9} |{:>9}",
+                term.name,
+                term.white_middle_game,
+                term.white_end_game,
+                term.black_middle_game,
+                term.black_end_game,
+                term.total(trace.phase)
+            );
+        }
+        println!();
+        println!(
+            "Phase: {} of {MAX_PHASE}, middlegame weight {}%",
+            trace.phase,
+            trace.phase * 100 / MAX_PHASE
+        );
+        println!("Material and piece squares: {}", trace.total);
+    }
+    println!("Final evaluation ({evaluator}): {evaluation} (white side)");
+}
+
+/// Writes a move played in the position in UCI notation.
+/// cozy-chess represents castling as the king capturing its own rook, which is how
+/// Chess960 castling is sent, while standard castling is sent as the king moving two squares
+pub fn uci_move(board: &VariantBoard, played_move: Move, chess960: bool) -> String {
+    if let VariantBoard::Standard(board) = board {
+        let side_to_move = board.side_to_move();
+        let castles = board
+            .colored_pieces(side_to_move, Piece::King)
+            .has(played_move.from)
+            && board
+                .colored_pieces(side_to_move, Piece::Rook)
+                .has(played_move.to);
+        if castles && !chess960 {
+            let king_file = match played_move.to.file() > played_move.from.file() {
+                true => File::G,
+                false => File::C,
+            };
+            let king_move = Move {
+                to: Square::new(king_file, played_move.from.rank()),
+                ..played_move
+            };
+            return king_move.to_string();
+        }
+    }
+    played_move.to_string()
+}
+
+/// Writes a line of moves starting from the position in UCI notation
+pub fn uci_line(board: &VariantBoard, line: &[Move], chess960: bool) -> String {
+    let mut board = board.clone();
+    let mut uci_moves = Vec::new();
+    for played_move in line {
+        uci_moves.push(uci_move(&board, *played_move, chess960));
+        board.play(*played_move);
+    }
+    uci_moves.join(" ")
+}
+
+/// Converts an evaluation for white into a UCI score from the view of the side to move.
+/// Won positions are sent as a mate in the number of moves the win is away
+pub fn score(evaluation: i32, side_to_move: Color) -> String {
+    match (win_distance(evaluation), side_to_move) {
+        (Some((winner, plies)), _) if winner == side_to_move => {
+            format!("mate {}", (plies + 1) / 2)
+        }
+        (Some((_, plies)), _) => format!("mate -{}", (plies + 1) / 2),
+        (None, Color::White) => format!("cp {evaluation}"),
+        (None, Color::Black) => format!("cp {}", -evaluation),
+    }
+}
+
+/// Sends the result of a completed search iteration
+pub fn search_info(
+    depth: i32,
+    seldepth: i32,
+    score: &str,
+    nodes: u64,
+    elapsed: Duration,
+    pv: &str,
+) {
+    let millis = elapsed.as_millis();
+    let nps = nodes as u128 * 1000 / millis.max(1);
+    send_info(&format!(
+        "depth {depth} seldepth {seldepth} score {score} nodes {nodes} nps {nps} time {millis} pv {pv}"
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_is_relative_to_side_to_move() {
+        assert_eq!(score(120, Color::White), "cp 120");
+        assert_eq!(score(120, Color::Black), "cp -120");
+        assert_eq!(score(-35, Color::Black), "cp 35");
+    }
+
+    #[test]
+    fn test_won_positions_are_mate_scores() {
+        assert_eq!(score(i32::MAX - 3, Color::White), "mate 2");
+        assert_eq!(score(i32::MAX - 3, Color::Black), "mate -2");
+        assert_eq!(score(i32::MIN + 4, Color::Black), "mate 2");
+        assert_eq!(score(i32::MIN + 1, Color::White), "mate -1");
+    }
+
+    #[test]
+    fn test_castling_notation() {
+        let board = VariantBoard::Standard("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1".parse().unwrap());
+        let short = "e1h1".parse().unwrap();
+        let long = "e1a1".parse().unwrap();
+        assert_eq!(uci_move(&board, short, false), "e1g1");
+        assert_eq!(uci_move(&board, long, false), "e1c1");
+        assert_eq!(uci_move(&board, short, true), "e1h1");
+        let line = ["e1h1".parse().unwrap(), "e8a8".parse().unwrap()];
+        assert_eq!(uci_line(&board, &line, false), "e1g1 e8c8");
+        // A rook taking a rook is not castling
+        let rook_capture = "a1a8".parse().unwrap();
+        assert_eq!(uci_move(&board, rook_capture, false), "a1a8");
+    }
 }
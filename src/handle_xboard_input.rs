@@ -0,0 +1,536 @@
+use cozy_chess::Color;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::engine::get_move;
+use crate::handle_uci_input::{default_game, parse_uci_move};
+use crate::handle_uci_output::uci_move;
+use crate::handle_xboard_output;
+use crate::play::{Game, Protocol};
+use crate::time_management::SearchLimits;
+use crate::variant::{Variant, VariantBoard};
+
+/// The clock settings of the level command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Level {
+    /// The number of moves to play before the clock is refilled, 0 for the whole game
+    pub moves_per_session: u32,
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+/// A game played over the xboard protocol. Unlike UCI the engine keeps track
+/// of the game itself, and decides when it is its turn to move
+#[derive(Clone)]
+pub struct XboardGame {
+    pub game: Game,
+    /// The colour the engine plays, None in force mode where it only follows the moves
+    pub engine_color: Option<Color>,
+    pub level: Option<Level>,
+    /// Set by st, the time to think on every move
+    pub move_time: Option<Duration>,
+    /// Set by sd, the deepest iteration to search
+    pub depth: Option<i32>,
+    pub engine_clock: Option<Duration>,
+    pub opponent_clock: Option<Duration>,
+    /// The number of moves the engine played since the game started, used with level
+    pub engine_moves: u32,
+}
+
+pub fn default_xboard_game() -> XboardGame {
+    XboardGame {
+        game: reset_board(
+            VariantBoard::startpos(Variant::Standard),
+            Game {
+                protocol: Protocol::Xboard { post: false },
+                ..default_game()
+            },
+        ),
+        engine_color: Some(Color::Black),
+        level: None,
+        move_time: None,
+        depth: None,
+        engine_clock: None,
+        opponent_clock: None,
+        engine_moves: 0,
+    }
+}
+
+pub fn protover(xboard: XboardGame) -> XboardGame {
+    handle_xboard_output::features();
+    xboard
+}
+
+pub fn ping(number: &str, xboard: XboardGame) -> XboardGame {
+    handle_xboard_output::pong(number);
+    xboard
+}
+
+/// Starts a new game of standard chess with the engine playing black
+pub fn new_game(xboard: XboardGame) -> XboardGame {
+    let xboard = abandon_search(xboard);
+    xboard.game.transposition_table.lock().unwrap().clear();
+    XboardGame {
+        game: reset_board(
+            VariantBoard::startpos(Variant::Standard),
+            Game {
+                variant: Variant::Standard,
+                ..xboard.game
+            },
+        ),
+        engine_color: Some(Color::Black),
+        depth: None,
+        engine_clock: None,
+        opponent_clock: None,
+        engine_moves: 0,
+        ..xboard
+    }
+}
+
+/// Switches the new game to a variant. Giveaway is what UCI calls antichess
+pub fn variant(name: &str, xboard: XboardGame) -> XboardGame {
+    let variant = match name {
+        "normal" => Variant::Standard,
+        "giveaway" => Variant::Antichess,
+        "suicide" => Variant::Suicide,
+        _ => {
+            handle_xboard_output::error("unsupported variant", &format!("variant {name}"));
+            return xboard;
+        }
+    };
+    let xboard = abandon_search(xboard);
+    // Positions and search results of the old variant mean nothing in the new one
+    xboard.game.transposition_table.lock().unwrap().clear();
+    XboardGame {
+        game: reset_board(
+            VariantBoard::startpos(variant),
+            Game {
+                variant,
+                ..xboard.game
+            },
+        ),
+        ..xboard
+    }
+}
+
+/// Sets up the board from a FEN in the current variant
+pub fn set_board(fen: &str, xboard: XboardGame) -> XboardGame {
+    let xboard = abandon_search(xboard);
+    let board = match VariantBoard::from_fen(fen, xboard.game.variant) {
+        Ok(board) => board,
+        Err(_) => {
+            handle_xboard_output::tell_user_error("Illegal position");
+            return xboard;
+        }
+    };
+    XboardGame {
+        game: reset_board(board, xboard.game),
+        engine_moves: 0,
+        ..xboard
+    }
+}
+
+/// Stops playing either side, moves are only recorded until go is received
+pub fn force(xboard: XboardGame) -> XboardGame {
+    XboardGame {
+        engine_color: None,
+        ..abandon_search(xboard)
+    }
+}
+
+/// The game is over, so the engine stops playing until the next one starts
+pub fn result(xboard: XboardGame) -> XboardGame {
+    force(xboard)
+}
+
+/// Plays the side to move and starts thinking
+pub fn go(xboard: XboardGame) -> XboardGame {
+    let xboard = collect_engine_move(xboard);
+    let xboard = XboardGame {
+        engine_color: Some(xboard.game.board.side_to_move()),
+        ..xboard
+    };
+    start_search(xboard)
+}
+
+/// Plays the side that is not to move, and waits for the opponent's move
+pub fn play_other(xboard: XboardGame) -> XboardGame {
+    let xboard = collect_engine_move(xboard);
+    XboardGame {
+        engine_color: Some(!xboard.game.board.side_to_move()),
+        ..xboard
+    }
+}
+
+pub fn level(level: Level, xboard: XboardGame) -> XboardGame {
+    let xboard = collect_engine_move(xboard);
+    XboardGame {
+        level: Some(level),
+        move_time: None,
+        ..xboard
+    }
+}
+
+pub fn move_time(move_time: Duration, xboard: XboardGame) -> XboardGame {
+    let xboard = collect_engine_move(xboard);
+    XboardGame {
+        move_time: Some(move_time),
+        level: None,
+        ..xboard
+    }
+}
+
+pub fn depth(depth: i32, xboard: XboardGame) -> XboardGame {
+    let xboard = collect_engine_move(xboard);
+    XboardGame {
+        depth: Some(depth),
+        ..xboard
+    }
+}
+
+pub fn engine_time(time: Duration, xboard: XboardGame) -> XboardGame {
+    let xboard = collect_engine_move(xboard);
+    XboardGame {
+        engine_clock: Some(time),
+        ..xboard
+    }
+}
+
+pub fn opponent_time(time: Duration, xboard: XboardGame) -> XboardGame {
+    let xboard = collect_engine_move(xboard);
+    XboardGame {
+        opponent_clock: Some(time),
+        ..xboard
+    }
+}
+
+pub fn post(post: bool, xboard: XboardGame) -> XboardGame {
+    let xboard = collect_engine_move(xboard);
+    XboardGame {
+        game: Game {
+            protocol: Protocol::Xboard { post },
+            ..xboard.game
+        },
+        ..xboard
+    }
+}
+
+/// Plays the opponent's move, then starts thinking if it is the engine's turn
+pub fn user_move(text: &str, xboard: XboardGame) -> XboardGame {
+    let xboard = collect_engine_move(xboard);
+    if xboard.game.is_searching {
+        handle_xboard_output::error("command not legal now", &format!("usermove {text}"));
+        return xboard;
+    }
+    let Some(played_move) = parse_uci_move(&xboard.game.board, text, false) else {
+        handle_xboard_output::illegal_move(text);
+        return xboard;
+    };
+    let xboard = XboardGame {
+        game: play_move(played_move, xboard.game),
+        ..xboard
+    };
+    if xboard.engine_color == Some(xboard.game.board.side_to_move()) {
+        start_search(xboard)
+    } else {
+        xboard
+    }
+}
+
+/// Plays the best move found so far, if the engine is thinking
+pub fn move_now(xboard: XboardGame) -> XboardGame {
+    if !xboard.game.is_searching || !has_best_move(&xboard.game) {
+        return xboard;
+    }
+    if !xboard.game.stop_search.swap(true, Ordering::SeqCst) {
+        // The search had not finished, so its move is sent from here
+        send_engine_move(&xboard.game);
+    }
+    play_engine_move(xboard)
+}
+
+/// Records the engine's move once its search has finished and sent the move.
+/// The move is sent by the search thread, so every handler that reads or changes
+/// the game calls this first
+fn collect_engine_move(xboard: XboardGame) -> XboardGame {
+    if xboard.game.is_searching && xboard.game.stop_search.load(Ordering::SeqCst) {
+        play_engine_move(xboard)
+    } else {
+        xboard
+    }
+}
+
+/// Returns the limits of the engine's next search, using the clock of the side to move
+pub fn search_limits(xboard: &XboardGame) -> SearchLimits {
+    let mut limits = SearchLimits {
+        depth: xboard.depth,
+        move_time: xboard.move_time,
+        ..SearchLimits::default()
+    };
+    let base = xboard.level.map(|level| level.base);
+    let (Some(engine_time), Some(opponent_time)) =
+        (xboard.engine_clock.or(base), xboard.opponent_clock.or(base))
+    else {
+        return limits;
+    };
+    let increment = xboard.level.map(|level| level.increment);
+    match xboard.game.board.side_to_move() {
+        Color::White => {
+            limits.white_time = Some(engine_time);
+            limits.black_time = Some(opponent_time);
+        }
+        Color::Black => {
+            limits.white_time = Some(opponent_time);
+            limits.black_time = Some(engine_time);
+        }
+    }
+    limits.white_increment = increment;
+    limits.black_increment = increment;
+    if let Some(level) = xboard.level.filter(|level| level.moves_per_session > 0) {
+        limits.moves_to_go =
+            Some(level.moves_per_session - xboard.engine_moves % level.moves_per_session);
+    }
+    limits
+}
+
+/// Starts thinking on a separate thread, unless the game is over.
+/// The thread sends its move when it finishes, unless move_now got there first
+fn start_search(xboard: XboardGame) -> XboardGame {
+    // A search that is still running would send a second move for the same turn
+    let xboard = abandon_search(xboard);
+    let repetitions = xboard.game.previous_boards[&xboard.game.board.repetition_key()];
+    if xboard.game.board.outcome(repetitions).is_some() {
+        return xboard;
+    }
+    let limits = search_limits(&xboard);
+    let game = Game {
+        is_searching: true,
+        current_best_move: Some(Arc::new(Mutex::new(None))),
+        stop_search: Arc::new(AtomicBool::new(false)),
+        ..xboard.game
+    };
+    let search_game = game.clone();
+    thread::spawn(move || {
+        let searched_game = get_move(search_game, &limits);
+        // Whoever sets the stop flag first is responsible for sending the move
+        if !searched_game.stop_search.swap(true, Ordering::SeqCst) {
+            send_engine_move(&searched_game);
+        }
+    });
+    XboardGame { game, ..xboard }
+}
+
+/// Stops a search without playing its move, unless the move was already sent
+fn abandon_search(xboard: XboardGame) -> XboardGame {
+    if !xboard.game.is_searching {
+        return xboard;
+    }
+    if xboard.game.stop_search.swap(true, Ordering::SeqCst) {
+        return play_engine_move(xboard);
+    }
+    XboardGame {
+        game: Game {
+            is_searching: false,
+            current_best_move: None,
+            ..xboard.game
+        },
+        ..xboard
+    }
+}
+
+/// Returns true once the search has found a move to play
+fn has_best_move(game: &Game) -> bool {
+    let best_move = game.current_best_move.as_ref().unwrap();
+    let has_best_move = best_move.lock().unwrap().is_some();
+    has_best_move
+}
+
+/// Sends the best move of the search, along with the result if the move ends the game
+fn send_engine_move(game: &Game) {
+    let best_move = game.current_best_move.as_ref().unwrap();
+    let best_move = best_move.lock().unwrap().as_ref().unwrap().best_move;
+    handle_xboard_output::engine_move(&uci_move(&game.board, best_move, false));
+    let game_after = play_move(best_move, game.clone());
+    let repetitions = game_after.previous_boards[&game_after.board.repetition_key()];
+    if let Some(outcome) = game_after.board.outcome(repetitions) {
+        handle_xboard_output::result(outcome);
+    }
+}
+
+/// Plays the move the finished search sent
+fn play_engine_move(xboard: XboardGame) -> XboardGame {
+    let best_move = xboard.game.current_best_move.as_ref().unwrap();
+    let best_move = best_move.lock().unwrap().as_ref().unwrap().best_move;
+    let game = Game {
+        is_searching: false,
+        current_best_move: None,
+        ..xboard.game
+    };
+    XboardGame {
+        game: play_move(best_move, game),
+        engine_moves: xboard.engine_moves + 1,
+        ..xboard
+    }
+}
+
+/// Replaces the board, starting the game history afresh
+fn reset_board(board: VariantBoard, game: Game) -> Game {
+    let previous_boards = HashMap::from([(board.repetition_key(), 1)]);
+    Game {
+        board,
+        previous_boards,
+        ..game
+    }
+}
+
+/// Plays a legal move on the board and records the position it leads to
+fn play_move(played_move: cozy_chess::Move, game: Game) -> Game {
+    let mut board = game.board;
+    let mut previous_boards = game.previous_boards;
+    board.play(played_move);
+    *previous_boards.entry(board.repetition_key()).or_insert(0) += 1;
+    Game {
+        board,
+        previous_boards,
+        ..game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forced_game(moves: &[&str]) -> XboardGame {
+        let mut xboard = force(new_game(default_xboard_game()));
+        for played_move in moves {
+            xboard = user_move(played_move, xboard);
+        }
+        xboard
+    }
+
+    #[test]
+    fn test_force_mode_records_moves() {
+        let xboard = forced_game(&["e2e4", "e7e5", "e1e2"]);
+        assert_eq!(
+            xboard.game.board.to_string(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPPKPPP/RNBQ1BNR b kq - 1 2"
+        );
+        assert!(!xboard.game.is_searching);
+        // An illegal move is refused and leaves the board alone
+        let refused = user_move("e8e6", xboard.clone());
+        assert_eq!(refused.game.board, xboard.game.board);
+    }
+
+    /// Waits for the engine's search to send its move
+    fn wait_for_move(xboard: &XboardGame) {
+        while !xboard.game.stop_search.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_go_twice_plays_one_move() {
+        let xboard = move_time(Duration::from_secs(60), forced_game(&["e2e4"]));
+        let xboard = go(xboard);
+        let first_search = xboard.game.stop_search.clone();
+        let xboard = go(depth(1, xboard));
+        // The first search was stopped without sending its move
+        assert!(first_search.load(Ordering::SeqCst));
+        assert!(!Arc::ptr_eq(&first_search, &xboard.game.stop_search));
+        wait_for_move(&xboard);
+        let xboard = collect_engine_move(xboard);
+        assert!(!xboard.game.is_searching);
+        assert_eq!(xboard.engine_moves, 1);
+        assert_eq!(xboard.game.board.side_to_move(), Color::White);
+        assert_eq!(xboard.game.board.fullmove_number(), 2);
+    }
+
+    #[test]
+    fn test_engine_move_is_recorded_before_the_next_command() {
+        let xboard = go(depth(1, forced_game(&["e2e4"])));
+        wait_for_move(&xboard);
+        // The engine played black, so the side not to move is black again
+        let xboard = play_other(xboard);
+        assert_eq!(xboard.engine_moves, 1);
+        assert_eq!(xboard.game.board.side_to_move(), Color::White);
+        assert_eq!(xboard.engine_color, Some(Color::Black));
+        let xboard = go(xboard);
+        wait_for_move(&xboard);
+        let xboard = user_move("d2d4", xboard);
+        assert_eq!(xboard.engine_moves, 2);
+        assert_eq!(xboard.game.board.side_to_move(), Color::Black);
+    }
+
+    #[test]
+    fn test_castling_uses_king_destination() {
+        let xboard = set_board(
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+            force(default_xboard_game()),
+        );
+        let xboard = user_move("e1g1", xboard);
+        assert_eq!(
+            xboard.game.board.to_string(),
+            "r3k2r/8/8/8/8/8/8/R4RK1 b kq - 1 1"
+        );
+    }
+
+    #[test]
+    fn test_variants() {
+        let xboard = variant("giveaway", new_game(default_xboard_game()));
+        assert_eq!(xboard.game.variant, Variant::Antichess);
+        let xboard = variant("suicide", xboard);
+        assert_eq!(xboard.game.board, VariantBoard::startpos(Variant::Suicide));
+        let xboard = variant("crazyhouse", xboard);
+        assert_eq!(xboard.game.variant, Variant::Suicide);
+        assert_eq!(new_game(xboard).game.variant, Variant::Standard);
+    }
+
+    #[test]
+    fn test_variant_abandons_the_search() {
+        let xboard = move_time(Duration::from_secs(60), forced_game(&["e2e4"]));
+        let xboard = go(xboard);
+        let search = xboard.game.stop_search.clone();
+        let xboard = variant("giveaway", xboard);
+        assert!(search.load(Ordering::SeqCst));
+        assert!(!xboard.game.is_searching);
+        assert_eq!(
+            xboard.game.board,
+            VariantBoard::startpos(Variant::Antichess)
+        );
+        // The abandoned search's move is never played on the new board
+        let xboard = collect_engine_move(xboard);
+        assert_eq!(xboard.engine_moves, 0);
+        assert_eq!(
+            xboard.game.board,
+            VariantBoard::startpos(Variant::Antichess)
+        );
+    }
+
+    #[test]
+    fn test_search_limits() {
+        let xboard = level(
+            Level {
+                moves_per_session: 40,
+                base: Duration::from_secs(300),
+                increment: Duration::from_secs(2),
+            },
+            forced_game(&["e2e4"]),
+        );
+        let limits = search_limits(&xboard);
+        assert_eq!(limits.black_time, Some(Duration::from_secs(300)));
+        assert_eq!(limits.white_increment, Some(Duration::from_secs(2)));
+        assert_eq!(limits.moves_to_go, Some(40));
+        let xboard = engine_time(Duration::from_secs(10), xboard);
+        let xboard = opponent_time(Duration::from_secs(20), xboard);
+        let limits = search_limits(&xboard);
+        assert_eq!(limits.black_time, Some(Duration::from_secs(10)));
+        assert_eq!(limits.white_time, Some(Duration::from_secs(20)));
+        let xboard = depth(3, move_time(Duration::from_secs(1), xboard));
+        let limits = search_limits(&xboard);
+        assert_eq!(limits.move_time, Some(Duration::from_secs(1)));
+        assert_eq!(limits.depth, Some(3));
+    }
+}
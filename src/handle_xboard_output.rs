@@ -0,0 +1,79 @@
+use crate::engine::win_distance;
+use crate::variant::Outcome;
+use cozy_chess::Color;
+use std::time::Duration;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// The score xboard shows as a mate, the distance to mate in moves is added to it
+const MATE_SCORE: i32 = 100000;
+
+/// Implements the response to protover, telling the GUI what the engine supports
+pub fn features() {
+    println!(
+        "feature ping=1 setboard=1 playother=1 usermove=1 time=1 draw=0 sigint=0 sigterm=0 \
+         reuse=1 analyze=0 colors=0 name=0 variants=\"normal,giveaway,suicide\" \
+         myname=\"wheatleybot v.{VERSION}\" done=1"
+    );
+}
+
+pub fn pong(number: &str) {
+    println!("pong {number}");
+}
+
+/// Sends the move the engine plays
+pub fn engine_move(played_move: &str) {
+    println!("move {played_move}");
+}
+
+/// Claims the result of a game that ended with the engine's move
+pub fn result(outcome: Outcome) {
+    match outcome {
+        Outcome::Winner(Color::White) => println!("1-0 {{White wins}}"),
+        Outcome::Winner(Color::Black) => println!("0-1 {{Black wins}}"),
+        Outcome::Draw => println!("1/2-1/2 {{Draw}}"),
+    }
+}
+
+pub fn illegal_move(played_move: &str) {
+    println!("Illegal move: {played_move}");
+}
+
+/// Reports a command that could not be carried out
+pub fn error(reason: &str, command: &str) {
+    println!("Error ({reason}): {command}");
+}
+
+/// Shows a message to the user in a pop up
+pub fn tell_user_error(message: &str) {
+    println!("tellusererror {message}");
+}
+
+/// Converts an evaluation for white into a score for the side to move in centipawns.
+/// Won positions are sent as 100000 plus the number of moves to mate, as xboard expects
+pub fn score(evaluation: i32, side_to_move: Color) -> i32 {
+    match (win_distance(evaluation), side_to_move) {
+        (Some((winner, plies)), _) if winner == side_to_move => MATE_SCORE + (plies + 1) / 2,
+        (Some((_, plies)), _) => -MATE_SCORE - (plies + 1) / 2,
+        (None, Color::White) => evaluation,
+        (None, Color::Black) => -evaluation,
+    }
+}
+
+/// Sends the result of a completed search iteration as thinking output
+pub fn thinking(depth: i32, score: i32, elapsed: Duration, nodes: u64, pv: &str) {
+    let centiseconds = elapsed.as_millis() / 10;
+    println!("{depth} {score} {centiseconds} {nodes} {pv}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score() {
+        assert_eq!(score(120, Color::White), 120);
+        assert_eq!(score(120, Color::Black), -120);
+        assert_eq!(score(i32::MAX - 3, Color::White), 100002);
+        assert_eq!(score(i32::MAX - 4, Color::Black), -100002);
+    }
+}
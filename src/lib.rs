@@ -0,0 +1,40 @@
+//! wheatleybot, a chess engine that plays the worst moves it can find.
+//!
+//! The engine can be embedded through [`Engine`], or run as a UCI or XBoard
+//! engine by the wheatley_bot binary
+
+pub mod antichess;
+pub mod bench;
+pub mod chess960;
+pub mod draw_rules;
+pub mod engine;
+pub mod engine_api;
+pub mod engine_match;
+pub mod evaluator;
+pub mod game_runner;
+pub mod handle_uci_input;
+pub mod handle_uci_output;
+pub mod handle_xboard_input;
+pub mod handle_xboard_output;
+pub mod perft;
+pub mod pgn;
+pub mod play;
+pub mod position_stack;
+pub mod predicted_eval;
+pub mod san;
+pub mod selfplay;
+pub mod time_management;
+pub mod transposition_table;
+pub mod uci_command;
+pub mod uci_engine;
+pub mod uci_options;
+pub mod variant;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod xboard_command;
+
+pub use engine_api::Engine;
+pub use play::MoveEval;
+pub use time_management::SearchLimits;
+pub use uci_command::PositionStart;
+pub use variant::Variant;
@@ -1,51 +1,205 @@
 use std::io;
 use std::process::ExitCode;
-
-mod engine;
-mod handle_uci_input;
-mod handle_uci_output;
-mod play;
-mod predicted_eval;
+use wheatley_bot::game_runner::Score;
+use wheatley_bot::uci_command::{self, UciCommand, UciParseError};
+use wheatley_bot::xboard_command::{self, XboardCommand, XboardParseError};
+use wheatley_bot::{
+    bench, engine_match, handle_uci_input, handle_uci_output, handle_xboard_input, perft, selfplay,
+};
 
 fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some((subcommand, arguments)) = args.split_first() {
+        match subcommand.as_str() {
+            "selfplay" => return run_selfplay(arguments),
+            "match" => return run_match(arguments),
+            "perft" => return run_perft(arguments),
+            "bench" => return run_bench(arguments),
+            _ => {
+                eprintln!("Unknown subcommand [{subcommand}]");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
     println!("wheatleybot by Joseph Wilson");
-    let mut uci_input = String::new();
     let std_in = io::stdin();
-    std_in
-        .read_line(&mut uci_input)
-        .expect("Error When awaiting UCI signal");
-
-    if uci_input != "uci\n" {
-        handle_uci_output::send_info("Expected UCI Command to be given");
+    let first_line = loop {
+        match read_line(&std_in) {
+            Some(line) if line.trim().is_empty() => continue,
+            Some(line) => break line,
+            None => return ExitCode::FAILURE,
+        }
+    };
+    if xboard_command::parse(&first_line) == Ok(XboardCommand::Xboard) {
+        return run_xboard(&std_in);
+    }
+    if uci_command::parse(&first_line) != Ok(UciCommand::Uci) {
+        handle_uci_output::info_string("Expected UCI Command to be given");
         return ExitCode::FAILURE;
     }
     handle_uci_output::id();
+    run_uci(&std_in)
+}
+
+/// Answers UCI commands until the GUI quits
+fn run_uci(std_in: &io::Stdin) -> ExitCode {
     let mut game = handle_uci_input::default_game();
     loop {
-        let mut uci_command = String::new();
-        std_in
-            .read_line(&mut uci_command)
-            .expect("Error When awaiting UCI signal");
-        let uci_tokens: Vec<&str> = uci_command.split_whitespace().into_iter().collect();
-        game = match uci_tokens[0] {
-            "debug" => handle_uci_input::debug(game),
-            "isready" => handle_uci_input::is_ready(game),
-            "setoption" => handle_uci_input::set_option(&uci_tokens, game),
-            "register" => handle_uci_input::register(game),
-            "ucinewgame" => handle_uci_input::uci_new_game(),
-            "position" => handle_uci_input::position(&uci_tokens, game),
-            "go" => handle_uci_input::go(&uci_tokens, game),
-            "stop" => handle_uci_input::stop(game),
-            "ponderhit" => handle_uci_input::ponderhit(game),
-            "quit" => return ExitCode::SUCCESS,
-            _ => {
-                let first_word = uci_tokens[0];
-                let info_to_send = format!(
-                    "UCI Command [{first_word}] not recognized. Input string was {uci_command}"
-                );
-                handle_uci_output::send_info(&info_to_send);
+        // The GUI closed the input without sending quit
+        let Some(line) = read_line(std_in) else {
+            return ExitCode::SUCCESS;
+        };
+        let command = match uci_command::parse(&line) {
+            Ok(command) => command,
+            // Blank lines are sent by some GUIs and mean nothing
+            Err(UciParseError::Empty) => continue,
+            Err(error) => {
+                handle_uci_output::info_string(&error.to_string());
+                continue;
+            }
+        };
+        game = match command {
+            UciCommand::Uci => {
+                handle_uci_output::id();
                 game
             }
+            UciCommand::Debug(debug_mode) => handle_uci_input::debug(debug_mode, game),
+            UciCommand::IsReady => handle_uci_input::is_ready(game),
+            UciCommand::SetOption { name, value } => {
+                handle_uci_input::set_option(&name, value.as_deref(), game)
+            }
+            UciCommand::Register => handle_uci_input::register(game),
+            UciCommand::UciNewGame => handle_uci_input::uci_new_game(game),
+            UciCommand::Position { start, moves } => {
+                handle_uci_input::position(&start, &moves, game)
+            }
+            UciCommand::Go(limits) => handle_uci_input::go(limits, game),
+            UciCommand::Perft(depth) => handle_uci_input::perft(depth, game),
+            UciCommand::Bench(depth) => handle_uci_input::bench(depth, game),
+            UciCommand::Eval => handle_uci_input::eval(game),
+            UciCommand::Stop => handle_uci_input::stop(game),
+            UciCommand::PonderHit => handle_uci_input::ponderhit(game),
+            UciCommand::Quit => return ExitCode::SUCCESS,
+        }
+    }
+}
+
+/// Answers xboard commands until the GUI quits
+fn run_xboard(std_in: &io::Stdin) -> ExitCode {
+    let mut xboard = handle_xboard_input::default_xboard_game();
+    loop {
+        let Some(line) = read_line(std_in) else {
+            return ExitCode::SUCCESS;
+        };
+        let command = match xboard_command::parse(&line) {
+            Ok(command) => command,
+            Err(XboardParseError::Empty) => continue,
+            Err(error) => {
+                println!("{error}");
+                continue;
+            }
+        };
+        xboard = match command {
+            XboardCommand::Xboard | XboardCommand::Ignored => xboard,
+            XboardCommand::Protover(_) => handle_xboard_input::protover(xboard),
+            XboardCommand::New => handle_xboard_input::new_game(xboard),
+            XboardCommand::Variant(name) => handle_xboard_input::variant(&name, xboard),
+            XboardCommand::Force => handle_xboard_input::force(xboard),
+            XboardCommand::Go => handle_xboard_input::go(xboard),
+            XboardCommand::PlayOther => handle_xboard_input::play_other(xboard),
+            XboardCommand::Level {
+                moves_per_session,
+                base,
+                increment,
+            } => {
+                let level = handle_xboard_input::Level {
+                    moves_per_session,
+                    base,
+                    increment,
+                };
+                handle_xboard_input::level(level, xboard)
+            }
+            XboardCommand::St(move_time) => handle_xboard_input::move_time(move_time, xboard),
+            XboardCommand::Sd(depth) => handle_xboard_input::depth(depth, xboard),
+            XboardCommand::Time(time) => handle_xboard_input::engine_time(time, xboard),
+            XboardCommand::Otim(time) => handle_xboard_input::opponent_time(time, xboard),
+            XboardCommand::UserMove(text) => handle_xboard_input::user_move(&text, xboard),
+            XboardCommand::MoveNow => handle_xboard_input::move_now(xboard),
+            XboardCommand::Ping(number) => handle_xboard_input::ping(&number, xboard),
+            XboardCommand::SetBoard(fen) => handle_xboard_input::set_board(&fen, xboard),
+            XboardCommand::Result(_) => handle_xboard_input::result(xboard),
+            XboardCommand::Post => handle_xboard_input::post(true, xboard),
+            XboardCommand::NoPost => handle_xboard_input::post(false, xboard),
+            XboardCommand::Quit => return ExitCode::SUCCESS,
+        }
+    }
+}
+
+/// Plays the engine against itself with the settings given on the command line
+fn run_selfplay(arguments: &[String]) -> ExitCode {
+    let result = selfplay::parse_args(arguments).and_then(|config| selfplay::run(&config));
+    report_score("the first engine", result)
+}
+
+/// Plays the engine against an external UCI engine with the settings given on the command line
+fn run_match(arguments: &[String]) -> ExitCode {
+    let result = engine_match::parse_args(arguments).and_then(|config| engine_match::run(&config));
+    report_score("wheatleybot", result)
+}
+
+/// Counts the positions below every move of a position given on the command line
+fn run_perft(arguments: &[String]) -> ExitCode {
+    match perft::parse_args(arguments).and_then(|config| perft::run(&config)) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Searches the bench positions to the depth given on the command line
+fn run_bench(arguments: &[String]) -> ExitCode {
+    match bench::parse_args(arguments).and_then(bench::run) {
+        Ok(result) => {
+            handle_uci_output::bench(&result);
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn report_score(player: &str, result: Result<Score, String>) -> ExitCode {
+    match result {
+        Ok(score) => {
+            println!(
+                "Score of {player}: {} wins, {} draws, {} losses",
+                score.wins, score.draws, score.losses
+            );
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Reads the next line sent by the GUI, returning None once the input is closed.
+/// A line that is not valid UTF-8 is treated as blank, any other error reading the
+/// input ends it, as it would only repeat on every read
+fn read_line(std_in: &io::Stdin) -> Option<String> {
+    let mut line = String::new();
+    match std_in.read_line(&mut line) {
+        Ok(0) => None,
+        Ok(_) => Some(line),
+        Err(error) if error.kind() == io::ErrorKind::InvalidData => Some(String::new()),
+        Err(error) => {
+            eprintln!("Could not read the input: {error}");
+            None
         }
     }
 }
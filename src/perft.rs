@@ -0,0 +1,182 @@
+use crate::engine_api::Engine;
+use crate::game_runner::{parse_number, parse_option};
+use crate::handle_uci_output;
+use crate::uci_command::PositionStart;
+use crate::variant::{Variant, VariantBoard};
+use cozy_chess::Move;
+
+/// Counts the positions reached by playing every sequence of legal moves of the given length.
+/// Comparing the count with known values checks the move generation,
+/// including the ForcedCapture filter and the rules of the variant
+pub fn perft(board: &VariantBoard, depth: u32, forced_capture: bool) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = board.legal_moves(forced_capture);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    moves
+        .into_iter()
+        .map(|played_move| {
+            let mut child = board.clone();
+            child.play(played_move);
+            perft(&child, depth - 1, forced_capture)
+        })
+        .sum()
+}
+
+/// Counts the positions below every legal move, so that a wrong count can be
+/// traced to the move it comes from
+pub fn divide(board: &VariantBoard, depth: u32, forced_capture: bool) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    board
+        .legal_moves(forced_capture)
+        .into_iter()
+        .map(|played_move| {
+            let mut child = board.clone();
+            child.play(played_move);
+            (played_move, perft(&child, depth - 1, forced_capture))
+        })
+        .collect()
+}
+
+/// The settings of the perft subcommand
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PerftConfig {
+    pub depth: u32,
+    pub variant: Variant,
+    pub start: PositionStart,
+    /// UCI options set before counting, such as ForcedCapture
+    pub options: Vec<(String, Option<String>)>,
+}
+
+/// Reads the arguments of the perft subcommand, the depth followed by flags
+pub fn parse_args(args: &[String]) -> Result<PerftConfig, String> {
+    let (depth, args) = args
+        .split_first()
+        .ok_or_else(|| "perft requires a depth".to_string())?;
+    let mut config = PerftConfig {
+        depth: parse_number("the depth", depth)?,
+        ..PerftConfig::default()
+    };
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("{flag} requires a value"))
+        };
+        match flag.as_str() {
+            "--variant" => config.variant = value()?.parse()?,
+            "--fen" => config.start = PositionStart::Fen(value()?.clone()),
+            "--option" => config.options.push(parse_option(value()?)),
+            _ => return Err(format!("Unknown argument [{flag}]")),
+        }
+    }
+    Ok(config)
+}
+
+/// Prints the count below every move and the total, which is returned
+pub fn run(config: &PerftConfig) -> Result<u64, String> {
+    let mut engine = Engine::new();
+    engine.set_option("UCI_Variant", Some(&config.variant.to_string()))?;
+    for (name, value) in &config.options {
+        engine.set_option(name, value.as_deref())?;
+    }
+    engine.set_position(&config.start, &[])?;
+    let counts = engine.divide(config.depth);
+    handle_uci_output::divide(engine.board(), &counts, engine.chess960());
+    Ok(counts.iter().map(|(_, count)| count).sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(board: &VariantBoard, forced_capture: bool, max_depth: u32) -> Vec<u64> {
+        (1..=max_depth)
+            .map(|depth| perft(board, depth, forced_capture))
+            .collect()
+    }
+
+    #[test]
+    fn test_standard_perft() {
+        let startpos = VariantBoard::startpos(Variant::Standard);
+        assert_eq!(counts(&startpos, false, 4), [20, 400, 8902, 197281]);
+        let kiwipete = VariantBoard::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            Variant::Standard,
+        )
+        .unwrap();
+        assert_eq!(counts(&kiwipete, false, 3), [48, 2039, 97862]);
+        let promotions = VariantBoard::from_fen(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            Variant::Standard,
+        )
+        .unwrap();
+        assert_eq!(counts(&promotions, false, 3), [6, 264, 9467]);
+    }
+
+    #[test]
+    fn test_chess960_perft() {
+        let board = crate::chess960::from_fen(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        )
+        .unwrap();
+        let board = VariantBoard::Standard(board);
+        assert_eq!(counts(&board, false, 3), [21, 528, 12189]);
+    }
+
+    #[test]
+    fn test_antichess_perft() {
+        let startpos = VariantBoard::startpos(Variant::Antichess);
+        assert_eq!(counts(&startpos, false, 4), [20, 400, 8067, 153299]);
+        // Captures are compulsory in antichess whatever ForcedCapture is set to
+        assert_eq!(counts(&startpos, true, 4), [20, 400, 8067, 153299]);
+    }
+
+    #[test]
+    fn test_forced_capture_perft() {
+        // After 1. e4 d5 the only capture is exd5, after which Qxd5 is the only capture
+        let board = VariantBoard::from_fen(
+            "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2",
+            Variant::Standard,
+        )
+        .unwrap();
+        assert_eq!(counts(&board, true, 2), [1, 1]);
+        assert_eq!(perft(&board, 1, false), 31);
+        // Without a capture every move may be played
+        let startpos = VariantBoard::startpos(Variant::Standard);
+        assert_eq!(counts(&startpos, true, 2), [20, 400]);
+    }
+
+    #[test]
+    fn test_divide() {
+        let startpos = VariantBoard::startpos(Variant::Standard);
+        let moves = divide(&startpos, 3, false);
+        assert_eq!(moves.len(), 20);
+        assert_eq!(moves.iter().map(|(_, count)| count).sum::<u64>(), 8902);
+        let e4 = "e2e4".parse::<Move>().unwrap();
+        assert!(moves.contains(&(e4, 600)));
+        assert!(divide(&startpos, 0, false).is_empty());
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args: Vec<String> = ["3", "--variant", "antichess", "--option", "Hash=8"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let config = parse_args(&args).unwrap();
+        assert_eq!(config.depth, 3);
+        assert_eq!(config.variant, Variant::Antichess);
+        assert_eq!(
+            config.options,
+            [("Hash".to_string(), Some("8".to_string()))]
+        );
+        assert!(parse_args(&[]).is_err());
+        assert!(parse_args(&["three".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,515 @@
+use crate::engine::win_distance;
+use crate::handle_uci_output::uci_move;
+use crate::play::MoveEval;
+use crate::san::{self, SanError};
+use crate::uci_command::PositionStart;
+use crate::variant::{Outcome, Variant, VariantBoard};
+use cozy_chess::{Color, Move};
+use std::fmt;
+use std::str::FromStr;
+
+/// The longest line of move text written, as the PGN standard asks
+const MAX_LINE_LENGTH: usize = 79;
+
+/// A game as it is recorded in a PGN file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PgnGame {
+    /// The tags in the order they are written, starting with the seven tag roster
+    pub tags: Vec<(String, String)>,
+    /// The position the game started from
+    pub start: VariantBoard,
+    /// The moves played from the start
+    pub moves: Vec<Move>,
+    /// The comment written after each move, kept the same length as moves
+    pub comments: Vec<Option<String>>,
+}
+
+/// The reasons a PGN can not be read
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PgnError {
+    /// The text holds no game
+    Empty,
+    /// A tag is not written as [Name "value"]
+    InvalidTag(String),
+    UnknownVariant(String),
+    InvalidFen(String),
+    /// A move that could not be read in the position it was played in.
+    /// The number is the ply of the move, counting from 1
+    InvalidMove {
+        ply: usize,
+        error: SanError,
+    },
+    /// A comment, tag or variation is not closed
+    Unterminated,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnError::Empty => write!(f, "The PGN holds no game"),
+            PgnError::InvalidTag(tag) => write!(f, "Invalid tag [{tag}]"),
+            PgnError::UnknownVariant(variant) => write!(f, "Unknown variant [{variant}]"),
+            PgnError::InvalidFen(error) => write!(f, "Invalid FEN tag: {error}"),
+            PgnError::InvalidMove { ply, error } => write!(f, "{error} at ply {ply}"),
+            PgnError::Unterminated => write!(f, "A comment, tag or variation is not closed"),
+        }
+    }
+}
+
+impl PgnGame {
+    /// Creates a game without moves, with every tag of the seven tag roster unknown.
+    /// The variant and the starting position are tagged if they are not standard
+    pub fn new(start: VariantBoard) -> PgnGame {
+        let mut game = PgnGame {
+            tags: Vec::new(),
+            start,
+            moves: Vec::new(),
+            comments: Vec::new(),
+        };
+        for (name, value) in [
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "?"),
+            ("White", "?"),
+            ("Black", "?"),
+            ("Result", "*"),
+        ] {
+            game.set_tag(name, value);
+        }
+        let variant = game.start.variant();
+        match variant {
+            Variant::Standard => (),
+            Variant::Antichess => game.set_tag("Variant", "Antichess"),
+            Variant::Suicide => game.set_tag("Variant", "Suicide"),
+        }
+        if game.start != VariantBoard::startpos(variant) {
+            let fen = game.start.to_string();
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", &fen);
+        }
+        game
+    }
+
+    /// Returns the value of a tag
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets the value of a tag, adding it after the others if it is new
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag_name, _)| tag_name == name) {
+            Some((_, tag_value)) => *tag_value = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Records how the game ended, None for a game that is still being played
+    pub fn set_result(&mut self, outcome: Option<Outcome>) {
+        self.set_tag("Result", result_token(outcome));
+    }
+
+    /// Adds a move to the end of the game, with the comment written after it
+    pub fn push_move(&mut self, played_move: Move, comment: Option<String>) {
+        self.moves.push(played_move);
+        self.comments.push(comment);
+    }
+
+    /// Returns where the game starts, as given to the position command
+    pub fn start_position(&self) -> PositionStart {
+        match self.start == VariantBoard::startpos(self.start.variant()) {
+            true => PositionStart::StartPos,
+            false => PositionStart::Fen(self.start.to_string()),
+        }
+    }
+
+    /// Returns the moves of the game in UCI notation, as given to the position command
+    pub fn uci_moves(&self) -> Vec<String> {
+        let mut board = self.start.clone();
+        let mut uci_moves = Vec::new();
+        for played_move in &self.moves {
+            uci_moves.push(uci_move(&board, *played_move, false));
+            board.play(*played_move);
+        }
+        uci_moves
+    }
+}
+
+/// Writes the evaluation of a search as a comment, in pawns for white,
+/// or as the number of moves to mate with the sign of the winning side
+pub fn eval_comment(move_eval: &MoveEval) -> String {
+    match win_distance(move_eval.evaluation) {
+        Some((Color::White, plies)) => format!("[%eval #{}]", (plies + 1) / 2),
+        Some((Color::Black, plies)) => format!("[%eval #-{}]", (plies + 1) / 2),
+        None => format!("[%eval {:.2}]", move_eval.evaluation as f64 / 100.0),
+    }
+}
+
+/// Returns how a result is written in PGN
+pub fn result_token(outcome: Option<Outcome>) -> &'static str {
+    match outcome {
+        Some(Outcome::Winner(Color::White)) => "1-0",
+        Some(Outcome::Winner(Color::Black)) => "0-1",
+        Some(Outcome::Draw) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+impl fmt::Display for PgnGame {
+    /// Writes the game as PGN, with the move text in Standard Algebraic Notation
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(f, "[{name} \"{value}\"]")?;
+        }
+        writeln!(f)?;
+
+        let mut tokens = Vec::new();
+        let mut board = self.start.clone();
+        for (index, played_move) in self.moves.iter().enumerate() {
+            let move_number = board.fullmove_number();
+            match board.side_to_move() {
+                Color::White => tokens.push(format!("{move_number}.")),
+                Color::Black if index == 0 => tokens.push(format!("{move_number}...")),
+                Color::Black => (),
+            }
+            tokens.push(san::encode(&board, *played_move));
+            if let Some(Some(comment)) = self.comments.get(index) {
+                tokens.push(format!("{{{comment}}}"));
+            }
+            board.play(*played_move);
+        }
+        tokens.push(self.tag("Result").unwrap_or("*").to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+                writeln!(f, "{line}")?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        writeln!(f, "{line}")
+    }
+}
+
+impl FromStr for PgnGame {
+    type Err = PgnError;
+
+    /// Reads the first game of a PGN
+    fn from_str(text: &str) -> Result<PgnGame, PgnError> {
+        parse(text)?.into_iter().next().ok_or(PgnError::Empty)
+    }
+}
+
+/// The pieces a PGN is made of
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    /// A move, move number, annotation or result
+    Symbol(String),
+}
+
+/// Reads every game of a PGN.
+/// Variations and annotations are skipped, and comments are kept after the moves they follow
+pub fn parse(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut games = Vec::new();
+    let mut tags: Vec<(String, String)> = Vec::new();
+    // The game whose moves are being read, along with the position after them
+    let mut current: Option<(PgnGame, VariantBoard)> = None;
+    for token in tokenize(text)? {
+        match token {
+            Token::Tag(name, value) => {
+                // A tag after move text starts the next game
+                if let Some((game, _)) = current.take() {
+                    games.push(game);
+                }
+                tags.push((name, value));
+            }
+            Token::Comment(comment) => {
+                if let Some(last) = current
+                    .as_mut()
+                    .and_then(|(game, _)| game.comments.last_mut())
+                {
+                    *last = Some(comment);
+                }
+            }
+            Token::Symbol(symbol) => {
+                let (game, board) = match &mut current {
+                    Some(current) => current,
+                    None => {
+                        let game = start_game(std::mem::take(&mut tags))?;
+                        let board = game.start.clone();
+                        current.insert((game, board))
+                    }
+                };
+                if matches!(symbol.as_str(), "1-0" | "0-1" | "1/2-1/2" | "*") {
+                    game.set_tag("Result", &symbol);
+                    games.extend(current.take().map(|(game, _)| game));
+                    continue;
+                }
+                // Move numbers may be written against the move, as in 1.e4
+                let san = match symbol.split_once('.') {
+                    Some((number, san)) if number.chars().all(|c| c.is_ascii_digit()) => {
+                        san.trim_start_matches('.')
+                    }
+                    _ => &symbol,
+                };
+                if san.is_empty() || san.starts_with('$') {
+                    continue;
+                }
+                let played_move =
+                    san::decode(board, san).map_err(|error| PgnError::InvalidMove {
+                        ply: game.moves.len() + 1,
+                        error,
+                    })?;
+                board.play(played_move);
+                game.push_move(played_move, None);
+            }
+        }
+    }
+    games.extend(current.map(|(game, _)| game));
+    if !tags.is_empty() {
+        games.push(start_game(tags)?);
+    }
+    Ok(games)
+}
+
+/// Sets up a game from its tags, starting from the FEN tag in the variant of the Variant tag
+fn start_game(tags: Vec<(String, String)>) -> Result<PgnGame, PgnError> {
+    let tag = |name: &str| {
+        tags.iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let variant = match tag("Variant").map(str::to_ascii_lowercase).as_deref() {
+        None | Some("standard") | Some("chess") => Variant::Standard,
+        Some("antichess") | Some("giveaway") => Variant::Antichess,
+        Some("suicide") => Variant::Suicide,
+        Some(_) => {
+            return Err(PgnError::UnknownVariant(
+                tag("Variant").unwrap().to_string(),
+            ))
+        }
+    };
+    let start = match tag("FEN") {
+        Some(fen) => VariantBoard::from_fen(fen, variant).map_err(PgnError::InvalidFen)?,
+        None => VariantBoard::startpos(variant),
+    };
+    let mut game = PgnGame::new(start);
+    for (name, value) in &tags {
+        game.set_tag(name, value);
+    }
+    Ok(game)
+}
+
+/// Splits a PGN into tags, comments and symbols, skipping variations
+fn tokenize(text: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut variation_depth = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let comment = take_until(&mut chars, '}')?;
+                if variation_depth == 0 {
+                    tokens.push(Token::Comment(comment.trim().to_string()));
+                }
+            }
+            ';' => {
+                chars.by_ref().take_while(|c| *c != '\n').for_each(drop);
+            }
+            '(' => variation_depth += 1,
+            ')' => variation_depth -= 1,
+            '[' if variation_depth == 0 => {
+                let tag = take_until(&mut chars, ']')?;
+                tokens.push(parse_tag(&tag)?);
+            }
+            c if c.is_whitespace() => (),
+            c => {
+                let mut symbol = c.to_string();
+                while let Some(next) = chars.next_if(|next| {
+                    !next.is_whitespace() && !matches!(next, '{' | '}' | '(' | ')' | '[' | ';')
+                }) {
+                    symbol.push(next);
+                }
+                if variation_depth == 0 {
+                    tokens.push(Token::Symbol(symbol));
+                }
+            }
+        }
+    }
+    if variation_depth != 0 {
+        return Err(PgnError::Unterminated);
+    }
+    Ok(tokens)
+}
+
+/// Returns the text up to the closing character, which is skipped
+fn take_until(chars: &mut impl Iterator<Item = char>, end: char) -> Result<String, PgnError> {
+    let mut text = String::new();
+    for c in chars {
+        if c == end {
+            return Ok(text);
+        }
+        text.push(c);
+    }
+    Err(PgnError::Unterminated)
+}
+
+/// Reads the inside of a tag, Name "value"
+fn parse_tag(tag: &str) -> Result<Token, PgnError> {
+    let invalid = || PgnError::InvalidTag(tag.to_string());
+    let (name, value) = tag
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or_else(invalid)?;
+    let value = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or_else(invalid)?;
+    let value = value.replace("\\\"", "\"").replace("\\\\", "\\");
+    Ok(Token::Tag(name.to_string(), value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antichess::parse_move;
+    use crate::handle_uci_input::{default_game, position};
+    use crate::play::Game;
+    use cozy_chess::Board;
+
+    #[test]
+    fn test_write_game() {
+        let mut game = PgnGame::new(Board::startpos().into());
+        game.set_tag("White", "wheatleybot \"A\"");
+        for uci_move in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            game.push_move(uci_move.parse().unwrap(), None);
+        }
+        game.set_result(Some(Outcome::Winner(Color::Black)));
+        let expected = "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n\
+                        [White \"wheatleybot \\\"A\\\"\"]\n[Black \"?\"]\n[Result \"0-1\"]\n\n\
+                        1. f3 e5 2. g4 Qh4# 0-1\n";
+        assert_eq!(game.to_string(), expected);
+    }
+
+    #[test]
+    fn test_write_game_from_position() {
+        let start = VariantBoard::from_fen("8/8/8/8/8/8/p7/7K b - - 0 30", Variant::Antichess);
+        let mut game = PgnGame::new(start.unwrap());
+        game.push_move(parse_move("a2a1k").unwrap(), None);
+        let pgn = game.to_string();
+        assert!(pgn.contains("[Variant \"Antichess\"]\n[SetUp \"1\"]\n"));
+        assert!(pgn.contains("[FEN \"8/8/8/8/8/8/p7/7K b - - 0 30\"]\n"));
+        assert!(pgn.ends_with("\n30... a1=K *\n"));
+    }
+
+    #[test]
+    fn test_long_games_are_wrapped() {
+        let mut game = PgnGame::new(Board::startpos().into());
+        for _ in 0..20 {
+            for uci_move in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+                game.push_move(uci_move.parse().unwrap(), None);
+            }
+        }
+        let pgn = game.to_string();
+        let move_text = pgn.split("\n\n").nth(1).unwrap();
+        assert!(move_text.lines().count() > 1);
+        assert!(move_text.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+    }
+
+    #[test]
+    fn test_eval_comment() {
+        let move_eval = |evaluation| MoveEval {
+            evaluation,
+            best_move: "e2e4".parse().unwrap(),
+            pv: vec!["e2e4".parse().unwrap()],
+        };
+        assert_eq!(eval_comment(&move_eval(-35)), "[%eval -0.35]");
+        // The distance comes from the evaluation, the principal variation may be cut short
+        assert_eq!(eval_comment(&move_eval(i32::MAX - 3)), "[%eval #2]");
+        assert_eq!(eval_comment(&move_eval(i32::MIN + 1)), "[%eval #-1]");
+    }
+
+    #[test]
+    fn test_written_games_are_read_back() {
+        let start = VariantBoard::from_fen("8/8/8/8/8/8/p7/7K b - - 0 30", Variant::Suicide);
+        let mut game = PgnGame::new(start.unwrap());
+        game.set_tag("Annotator", "a \\ \"quoted\" name");
+        game.push_move(
+            parse_move("a2a1k").unwrap(),
+            Some("[%eval 1.00]".to_string()),
+        );
+        game.push_move(parse_move("h1g2").unwrap(), None);
+        game.set_result(Some(Outcome::Draw));
+        assert_eq!(game.to_string().parse(), Ok(game));
+    }
+
+    #[test]
+    fn test_read_games_from_other_programs() {
+        let text = "[Event \"Casual\"]\n[Result \"1-0\"]\n\n\
+                    1.e4 e5 2. Nf3 $1 (2. f4 {King's gambit} exf4) Nc6 ; main line\n\
+                    3. Bc4!? Nf6 4. 0-0 Nxe4 5. Bxf7+ 1-0\n\n\
+                    [Event \"Second\"]\n\n1. d4 *\n";
+        let games = parse(text).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("Event"), Some("Casual"));
+        assert_eq!(
+            games[0].uci_moves().join(" "),
+            "e2e4 e7e5 g1f3 b8c6 f1c4 g8f6 e1g1 f6e4 c4f7"
+        );
+        assert_eq!(games[1].tag("Result"), Some("*"));
+        assert_eq!(games[1].moves.len(), 1);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        assert_eq!("".parse::<PgnGame>(), Err(PgnError::Empty));
+        assert_eq!(
+            "1. e4 e4".parse::<PgnGame>(),
+            Err(PgnError::InvalidMove {
+                ply: 2,
+                error: SanError::Illegal("e4".to_string())
+            })
+        );
+        assert_eq!(
+            "1. e4 {never closed".parse::<PgnGame>(),
+            Err(PgnError::Unterminated)
+        );
+        assert!(matches!(
+            "[Variant \"Crazyhouse\"]\n1. e4 *".parse::<PgnGame>(),
+            Err(PgnError::UnknownVariant(_))
+        ));
+        assert!(matches!(
+            "[FEN \"8/8/8\"]\n*".parse::<PgnGame>(),
+            Err(PgnError::InvalidFen(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_games_are_playable_as_positions() {
+        let game: PgnGame = "[Variant \"Antichess\"]\n1. e3 b5 2. Bxb5 *"
+            .parse()
+            .unwrap();
+        let uci_game = Game {
+            variant: Variant::Antichess,
+            ..default_game()
+        };
+        let uci_game = position(&game.start_position(), &game.uci_moves(), uci_game);
+        let mut board = game.start.clone();
+        for played_move in &game.moves {
+            board.play(*played_move);
+        }
+        assert_eq!(uci_game.board, board);
+        assert_eq!(uci_game.board.fullmove_number(), 2);
+    }
+}
@@ -1,24 +1,57 @@
-use cozy_chess::{Board, Move};
+use crate::evaluator::EvaluatorKind;
+use crate::transposition_table::TranspositionTable;
+use crate::variant::{Variant, VariantBoard};
+use cozy_chess::Move;
 use std::collections::HashMap;
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct MoveEval {
     pub evaluation: i32,
     pub best_move: Move,
+    /// The line the engine expects to follow the best move, starting with the best move
+    pub pv: Vec<Move>,
+}
+
+/// The protocol the GUI speaks
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Protocol {
+    Uci,
+    /// The Chess Engine Communication Protocol used by XBoard and WinBoard.
+    /// post is weither thinking output is sent during a search
+    Xboard {
+        post: bool,
+    },
+    /// The engine is part of another program, which reads the results of a search
+    /// itself, so nothing is printed
+    Embedded,
 }
 
 #[derive(Clone)]
 pub struct Game {
     /// Weither to print more output when printing a move
     pub debug_mode: bool,
+    /// How to talk to the GUI
+    pub protocol: Protocol,
     /// If captures should be able to be forced
     pub forced_capture: bool,
+    /// The depth searched when the GUI gives no limits
+    pub depth: i32,
+    /// How reliably the worst move is found, from 0 to engine::MAX_SKILL
+    pub skill: i32,
+    /// How the search scores positions
+    pub evaluator: EvaluatorKind,
+    /// The rules the game is played by
+    pub variant: Variant,
+    /// If positions are Chess960 positions, which changes how castling is read and sent
+    pub chess960: bool,
     /// The current state of the board in the game
-    pub board: Board,
+    pub board: VariantBoard,
     /// A field to aid in collecting 3 and 5 move repetition
-    /// The keys of this hashmap are the hashes of the board
+    /// The keys of this hashmap are the repetition keys of the board,
+    /// see draw_rules::repetition_key
     /// This is not competly safe from collisions, but is likely
     /// good enough for our needs
     /// The values show the number of times that this
@@ -30,4 +63,10 @@ pub struct Game {
     pub current_best_move: Option<Arc<Mutex<Option<MoveEval>>>>,
     /// Weither to stop a search
     pub stop_search: Arc<AtomicBool>,
+    /// The thread running the search started by go, taken when it is joined
+    pub search_thread: Arc<Mutex<Option<JoinHandle<()>>>>,
+    /// Results of previous searches, kept between moves of the same game
+    pub transposition_table: Arc<Mutex<TranspositionTable>>,
+    /// The number of positions visited by the last search
+    pub nodes: u64,
 }
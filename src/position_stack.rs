@@ -0,0 +1,145 @@
+use crate::variant::VariantBoard;
+use cozy_chess::Move;
+use std::collections::HashMap;
+
+/// The positions on the path from the root of a search to the position being searched.
+/// Boards have no way to unmake a move, so playing a move copies the board into
+/// the next slot of the stack and undoing it drops that slot. The slots are reused,
+/// so a search does not allocate once the stack has grown to its deepest ply
+pub struct PositionStack {
+    /// The boards from the root, the last board is the current position
+    boards: Vec<VariantBoard>,
+    /// The repetition keys of the boards
+    keys: Vec<u64>,
+    /// The number of plies currently on the stack above the root
+    ply: usize,
+    /// The number of times each position was reached in the game before the root
+    game_history: HashMap<u64, i32>,
+}
+
+impl PositionStack {
+    /// Creates a stack rooted at the given board.
+    /// The game history holds the repetition counts of the game so far, which may include the root
+    pub fn new(root: VariantBoard, game_history: &HashMap<u64, i32>) -> PositionStack {
+        let mut game_history = game_history.clone();
+        let root_key = root.repetition_key();
+        if let Some(root_count) = game_history.get_mut(&root_key) {
+            // The root is counted as part of the stack
+            *root_count -= 1;
+        }
+        PositionStack {
+            boards: vec![root],
+            keys: vec![root_key],
+            ply: 0,
+            game_history,
+        }
+    }
+
+    /// Returns the current position
+    pub fn board(&self) -> &VariantBoard {
+        &self.boards[self.ply]
+    }
+
+    /// Returns the distance in plies between the root and the current position
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    /// Plays a legal move on the current position
+    pub fn play(&mut self, played_move: Move) {
+        let board = self.boards[self.ply].clone();
+        self.ply += 1;
+        if self.boards.len() == self.ply {
+            self.boards.push(board);
+            self.keys.push(0);
+        } else {
+            self.boards[self.ply] = board;
+        }
+        self.boards[self.ply].play(played_move);
+        self.keys[self.ply] = self.boards[self.ply].repetition_key();
+    }
+
+    /// Takes back the last move played
+    pub fn undo(&mut self) {
+        self.ply -= 1;
+    }
+
+    /// Returns the number of times the current position has been reached, including now.
+    /// Only positions since the last capture or pawn move can repeat, so the search
+    /// only looks back as far as the halfmove clock allows, and only at positions
+    /// with the same side to move
+    pub fn repetitions(&self) -> i32 {
+        let board = self.board();
+        let key = self.keys[self.ply];
+        let reversible_plies = board.halfmove_clock() as usize;
+        let mut count = 1;
+        let mut distance = 2;
+        while distance <= reversible_plies && distance <= self.ply {
+            if self.keys[self.ply - distance] == key {
+                count += 1;
+            }
+            distance += 2;
+        }
+        if reversible_plies > self.ply {
+            // The game before the root is within reach
+            count += self.game_history.get(&key).copied().unwrap_or(0);
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cozy_chess::Board;
+
+    fn play_all(stack: &mut PositionStack, moves: &[&str]) {
+        for played_move in moves {
+            stack.play(played_move.parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_play_and_undo() {
+        let mut stack = PositionStack::new(Board::startpos().into(), &HashMap::new());
+        play_all(&mut stack, &["e2e4", "e7e5"]);
+        assert_eq!(stack.ply(), 2);
+        stack.undo();
+        stack.play("c7c5".parse().unwrap());
+        let expected: Board = "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2"
+            .parse()
+            .unwrap();
+        assert_eq!(stack.board(), &expected.into());
+        stack.undo();
+        stack.undo();
+        assert_eq!(stack.board(), &Board::startpos().into());
+    }
+
+    #[test]
+    fn test_repetitions_within_search() {
+        let mut stack = PositionStack::new(Board::startpos().into(), &HashMap::new());
+        assert_eq!(stack.repetitions(), 1);
+        play_all(&mut stack, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert_eq!(stack.repetitions(), 2);
+        play_all(&mut stack, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert_eq!(stack.repetitions(), 3);
+    }
+
+    #[test]
+    fn test_repetitions_include_game_history() {
+        let root: Board = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 8 5"
+            .parse()
+            .unwrap();
+        // The root has been reached once before in the game
+        let game_history = HashMap::from([(root.hash(), 2)]);
+        let mut stack = PositionStack::new(root.into(), &game_history);
+        play_all(&mut stack, &["b1c3", "b8c6", "c3b1", "c6b8"]);
+        assert_eq!(stack.repetitions(), 3);
+        // A pawn move makes every earlier position unreachable
+        play_all(
+            &mut stack,
+            &["e2e3", "e7e6", "g1f3", "g8f6", "f3g1", "f6g8"],
+        );
+        assert_eq!(stack.repetitions(), 2);
+    }
+}
@@ -51,6 +51,17 @@ const QUEEN_PIECE_SQUARE_TABLE: [[i8; 8]; 8] = [
     [-10, 0, 5, 0, 0, 0, 0, -10],
     [-20, -10, -10, -5, -5, -10, -10, -20],
 ];
+/// Pawns are worth more the closer they are to promoting once the pieces are gone
+const PAWN_END_GAME_PIECE_SQUARE_TABLE: [[i8; 8]; 8] = [
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [80, 80, 80, 80, 80, 80, 80, 80],
+    [50, 50, 50, 50, 50, 50, 50, 50],
+    [30, 30, 30, 30, 30, 30, 30, 30],
+    [20, 20, 20, 20, 20, 20, 20, 20],
+    [10, 10, 10, 10, 10, 10, 10, 10],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+];
 const KING_MIDDLE_GAME_PIECE_SQUARE_TABLE: [[i8; 8]; 8] = [
     [-30, -40, -40, -50, -50, -40, -40, -30],
     [-30, -40, -40, -50, -50, -40, -40, -30],
@@ -61,168 +72,275 @@ const KING_MIDDLE_GAME_PIECE_SQUARE_TABLE: [[i8; 8]; 8] = [
     [20, 20, 0, 0, 0, 0, 20, 20],
     [20, 30, 10, 0, 0, 10, 30, 20],
 ];
+const KING_END_GAME_PIECE_SQUARE_TABLE: [[i8; 8]; 8] = [
+    [-50, -40, -30, -20, -20, -30, -40, -50],
+    [-30, -20, -10, 0, 0, -10, -20, -30],
+    [-30, -10, 20, 30, 30, 20, -10, -30],
+    [-30, -10, 30, 40, 40, 30, -10, -30],
+    [-30, -10, 30, 40, 40, 30, -10, -30],
+    [-30, -10, 20, 30, 30, 20, -10, -30],
+    [-30, -30, 0, 0, 0, 0, -30, -30],
+    [-50, -30, -30, -30, -30, -30, -30, -50],
+];
+
+/// The game phase with every piece on the board. Promotions can push the count
+/// above this, so it is capped
+pub const MAX_PHASE: i32 = 24;
+
+/// The stage of the game a piece square table is meant for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameStage {
+    MiddleGame,
+    EndGame,
+}
 
 /// Returns the predicted evaluation of a particular board for white in centipawns
 pub fn get_truncated_eval(board: &Board) -> i32 {
     get_material_evaluation(board) + get_piece_square_evaluation(board)
 }
 
-struct RowAndCol {
-    row: usize,
-    col: usize,
+/// The values of one term of the evaluation for each colour, from that colour's view
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalTerm {
+    pub name: String,
+    pub white_middle_game: i32,
+    pub white_end_game: i32,
+    pub black_middle_game: i32,
+    pub black_end_game: i32,
 }
 
-/// Returns the material evaluation of a particular board for white in centipawns
+impl EvalTerm {
+    /// The term's contribution to the evaluation for white, blended by the phase
+    pub fn total(&self, phase: i32) -> i32 {
+        taper(
+            self.white_middle_game - self.black_middle_game,
+            self.white_end_game - self.black_end_game,
+            phase,
+        )
+    }
+}
+
+/// The terms that make up the evaluation of a position, for explaining a score
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalTrace {
+    /// The game phase the middlegame and endgame values are blended by
+    pub phase: i32,
+    pub terms: Vec<EvalTerm>,
+    /// The evaluation for white, which is get_truncated_eval.
+    /// Rounding can make it differ slightly from the sum of the terms
+    pub total: i32,
+}
+
+/// Breaks the evaluation of the board down into material and piece square terms for every piece
+pub fn trace(board: &Board) -> EvalTrace {
+    let mut terms = Vec::new();
+    for piece in Piece::ALL {
+        let material = |color| board.colored_pieces(color, piece).len() as i32 * piece_value(piece);
+        let (white, black) = (material(Color::White), material(Color::Black));
+        terms.push(EvalTerm {
+            name: format!("{piece:?} material"),
+            white_middle_game: white,
+            white_end_game: white,
+            black_middle_game: black,
+            black_end_game: black,
+        });
+    }
+    for piece in Piece::ALL {
+        let squares = |color, stage| get_piece_type_square_evaluation(board, piece, color, stage);
+        terms.push(EvalTerm {
+            name: format!("{piece:?} squares"),
+            white_middle_game: squares(Color::White, GameStage::MiddleGame),
+            white_end_game: squares(Color::White, GameStage::EndGame),
+            black_middle_game: squares(Color::Black, GameStage::MiddleGame),
+            black_end_game: squares(Color::Black, GameStage::EndGame),
+        });
+    }
+    EvalTrace {
+        phase: game_phase(board),
+        terms,
+        total: get_truncated_eval(board),
+    }
+}
+
+/// Returns the value of a piece in centipawns
 /// Uses standard material weights
 ///     Pawns == 100 centipawns
 ///     Knights == 250 centipawns
 ///     Bishiops == 300 centipawns
 ///     Rooks == 500 centipawns
 ///     Queens == 900 centipawns
-fn get_material_evaluation(board: &Board) -> i32 {
-    let mut material: i32 = 0;
-    material += board.colored_pieces(Color::White, Piece::Pawn).len() as i32 * 100;
-    material += board.colored_pieces(Color::White, Piece::Knight).len() as i32 * 250;
-    material += board.colored_pieces(Color::White, Piece::Bishop).len() as i32 * 300;
-    material += board.colored_pieces(Color::White, Piece::Rook).len() as i32 * 500;
-    material += board.colored_pieces(Color::White, Piece::Queen).len() as i32 * 900;
+/// The king can not be lost, so it is worth nothing
+pub fn piece_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::Pawn => 100,
+        Piece::Knight => 250,
+        Piece::Bishop => 300,
+        Piece::Rook => 500,
+        Piece::Queen => 900,
+        Piece::King => 0,
+    }
+}
 
-    material -= board.colored_pieces(Color::Black, Piece::Pawn).len() as i32 * 100;
-    material -= board.colored_pieces(Color::Black, Piece::Knight).len() as i32 * 250;
-    material -= board.colored_pieces(Color::Black, Piece::Bishop).len() as i32 * 300;
-    material -= board.colored_pieces(Color::Black, Piece::Rook).len() as i32 * 500;
-    material -= board.colored_pieces(Color::Black, Piece::Queen).len() as i32 * 900;
-    return material;
+/// Returns the material evaluation of a particular board for white in centipawns
+pub fn get_material_evaluation(board: &Board) -> i32 {
+    Piece::ALL
+        .iter()
+        .map(|piece| {
+            let white = board.colored_pieces(Color::White, *piece).len() as i32;
+            let black = board.colored_pieces(Color::Black, *piece).len() as i32;
+            (white - black) * piece_value(*piece)
+        })
+        .sum()
 }
 
 /// Returns the value of the positions that the pieces are in
 /// The percice piece square tables are declared
-/// Again evaluates with respect to white, negate for black
+/// Again evaluates with respect to white, negate for black.
+/// The middlegame and endgame tables are blended by how much material is left
 fn get_piece_square_evaluation(board: &Board) -> i32 {
-    let mut value: i32 = 0;
-    value += get_pawn_piece_square_evaluation(board);
-    value += get_rook_piece_square_evaluation(board);
-    value += get_bishiop_piece_square_evaluation(board);
-    value += get_knight_piece_square_evaluation(board);
-    value += get_queen_piece_square_evaluation(board);
-    value += get_king_piece_square_evaluation(board);
-    return value;
+    let stage_evaluation = |stage| -> i32 {
+        Piece::ALL
+            .iter()
+            .map(|piece| {
+                get_piece_type_square_evaluation(board, *piece, Color::White, stage)
+                    - get_piece_type_square_evaluation(board, *piece, Color::Black, stage)
+            })
+            .sum()
+    };
+    taper(
+        stage_evaluation(GameStage::MiddleGame),
+        stage_evaluation(GameStage::EndGame),
+        game_phase(board),
+    )
 }
 
-fn get_pawn_piece_square_evaluation(board: &Board) -> i32 {
-    let mut pawn_value = 0;
-    let white_pawns = board.colored_pieces(Color::White, Piece::Pawn).into_iter();
-    for pawn in white_pawns {
-        let index = index_to_row_and_col(pawn);
-        pawn_value += PAWN_PIECE_SQUARE_TABLE[index.row][index.col] as i32;
-    }
-    let black_pawns = board.colored_pieces(Color::Black, Piece::Pawn).into_iter();
-    for pawn in black_pawns {
-        let index = index_to_row_and_col(pawn);
-        pawn_value -= PAWN_PIECE_SQUARE_TABLE[7 - index.row][7 - index.col] as i32;
-    }
-    pawn_value
+/// Returns the value of the positions of one colour's pieces of one kind,
+/// from the view of that colour
+pub fn get_piece_type_square_evaluation(
+    board: &Board,
+    piece: Piece,
+    color: Color,
+    stage: GameStage,
+) -> i32 {
+    board
+        .colored_pieces(color, piece)
+        .into_iter()
+        .map(|square| piece_square_value(piece, color, square, stage))
+        .sum()
 }
 
-fn get_bishiop_piece_square_evaluation(board: &Board) -> i32 {
-    let mut bishop_value = 0;
-    let white_bishiops = board
-        .colored_pieces(Color::White, Piece::Bishop)
-        .into_iter();
-    for bishiop in white_bishiops {
-        let index = index_to_row_and_col(bishiop);
-        bishop_value += BISHIOP_PIECE_SQUARE_TABLE[7 - index.row][7 - index.col] as i32;
-    }
-    let black_bishiops = board
-        .colored_pieces(Color::Black, Piece::Bishop)
-        .into_iter();
-    for bishiop in black_bishiops {
-        let index = index_to_row_and_col(bishiop);
-        bishop_value -= BISHIOP_PIECE_SQUARE_TABLE[7 - index.row][7 - index.col] as i32;
-    }
-    bishop_value
+/// Returns how far the game is from the endgame, from MAX_PHASE when no piece has been
+/// traded down to 0 when only kings and pawns are left.
+/// Minor pieces count 1, rooks 2 and queens 4
+pub fn game_phase(board: &Board) -> i32 {
+    let phase: i32 = Piece::ALL
+        .iter()
+        .map(|piece| {
+            let weight = match piece {
+                Piece::Knight | Piece::Bishop => 1,
+                Piece::Rook => 2,
+                Piece::Queen => 4,
+                Piece::Pawn | Piece::King => 0,
+            };
+            board.pieces(*piece).len() as i32 * weight
+        })
+        .sum();
+    phase.min(MAX_PHASE)
 }
 
-fn get_knight_piece_square_evaluation(board: &Board) -> i32 {
-    let mut knight_value = 0;
-    let white_knights = board
-        .colored_pieces(Color::White, Piece::Knight)
-        .into_iter();
-    for knight in white_knights {
-        let index = index_to_row_and_col(knight);
-        knight_value += KNIGHT_PIECE_SQUARE_TABLE[index.row][index.col] as i32;
-    }
-    let black_knights = board
-        .colored_pieces(Color::Black, Piece::Knight)
-        .into_iter();
-    for knight in black_knights {
-        let index = index_to_row_and_col(knight);
-        knight_value -= KNIGHT_PIECE_SQUARE_TABLE[7 - index.row][7 - index.col] as i32;
-    }
-    knight_value
+/// Blends a middlegame and an endgame evaluation by the game phase
+pub fn taper(middle_game: i32, end_game: i32, phase: i32) -> i32 {
+    (middle_game * phase + end_game * (MAX_PHASE - phase)) / MAX_PHASE
 }
 
-fn get_rook_piece_square_evaluation(board: &Board) -> i32 {
-    let mut rook_value = 0;
-    let white_rooks = board.colored_pieces(Color::White, Piece::Rook).into_iter();
-    for rook in white_rooks {
-        let index = index_to_row_and_col(rook);
-        rook_value += ROOK_PIECE_SQUARE_TABLE[index.row][index.col] as i32;
-    }
-    let black_rooks = board.colored_pieces(Color::Black, Piece::Rook).into_iter();
-    for rook in black_rooks {
-        let index = index_to_row_and_col(rook);
-        rook_value -= ROOK_PIECE_SQUARE_TABLE[7 - index.row][7 - index.col] as i32;
-    }
-    rook_value
+/// Looks up the piece square table of the piece for a piece of the colour on the square.
+/// The tables are written from white's side of the board, with the eighth rank on the
+/// first row, so black pieces read them with the ranks flipped
+fn piece_square_value(piece: Piece, color: Color, square: Square, stage: GameStage) -> i32 {
+    let rank = square.rank().relative_to(color) as usize;
+    let file = square.file() as usize;
+    piece_square_table(piece, stage)[7 - rank][file] as i32
 }
 
-fn get_queen_piece_square_evaluation(board: &Board) -> i32 {
-    let mut queen_value = 0;
-    let white_queens = board.colored_pieces(Color::White, Piece::Rook).into_iter();
-    for queen in white_queens {
-        let index = index_to_row_and_col(queen);
-        queen_value += QUEEN_PIECE_SQUARE_TABLE[index.row][index.col] as i32;
+/// Only pawns and kings change their roles in the endgame, the other pieces
+/// use the same table throughout
+fn piece_square_table(piece: Piece, stage: GameStage) -> &'static [[i8; 8]; 8] {
+    match (piece, stage) {
+        (Piece::Pawn, GameStage::MiddleGame) => &PAWN_PIECE_SQUARE_TABLE,
+        (Piece::Pawn, GameStage::EndGame) => &PAWN_END_GAME_PIECE_SQUARE_TABLE,
+        (Piece::Knight, _) => &KNIGHT_PIECE_SQUARE_TABLE,
+        (Piece::Bishop, _) => &BISHIOP_PIECE_SQUARE_TABLE,
+        (Piece::Rook, _) => &ROOK_PIECE_SQUARE_TABLE,
+        (Piece::Queen, _) => &QUEEN_PIECE_SQUARE_TABLE,
+        (Piece::King, GameStage::MiddleGame) => &KING_MIDDLE_GAME_PIECE_SQUARE_TABLE,
+        (Piece::King, GameStage::EndGame) => &KING_END_GAME_PIECE_SQUARE_TABLE,
     }
-    let black_queens = board.colored_pieces(Color::Black, Piece::Rook).into_iter();
-    for queen in black_queens {
-        let index = index_to_row_and_col(queen);
-        queen_value -= QUEEN_PIECE_SQUARE_TABLE[7 - index.row][7 - index.col] as i32;
-    }
-    queen_value
 }
 
-/// Returns the evaluation in centipawns of the position of the kings on the board.
-/// Like all piece square evaluations, this evaluation is from the view of the player playing white
-fn get_king_piece_square_evaluation(board: &Board) -> i32 {
-    let mut king_value = 0;
-    // Decide if we are in the endgame. Lets just assume always middlegame
-    //TODO - Determine endgame
-    let white_king = board.colored_pieces(Color::White, Piece::Rook).into_iter();
-    for king in white_king {
-        let index = index_to_row_and_col(king);
-        king_value += KING_MIDDLE_GAME_PIECE_SQUARE_TABLE[index.row][index.col] as i32;
-    }
-    let black_king = board.colored_pieces(Color::Black, Piece::Rook).into_iter();
-    for king in black_king {
-        let index = index_to_row_and_col(king);
-        king_value -= KING_MIDDLE_GAME_PIECE_SQUARE_TABLE[7 - index.row][7 - index.col] as i32;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pawn_evaluation(board: &Board) -> i32 {
+        let value = |color| {
+            get_piece_type_square_evaluation(board, Piece::Pawn, color, GameStage::MiddleGame)
+        };
+        value(Color::White) - value(Color::Black)
     }
-    king_value
-}
 
-/// Converts an index to a row and column
-fn index_to_row_and_col(square: Square) -> RowAndCol {
-    let index = square as usize;
-    RowAndCol {
-        row: 7 - (index / 8),
-        col: 7 - (index % 8),
+    /// Flips the board from top to bottom and swaps the colours of every piece,
+    /// which should exactly negate the evaluation
+    fn mirror(fen: &str) -> Board {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let swap_case = |text: &str| -> String {
+            text.chars()
+                .map(|c| match c.is_ascii_uppercase() {
+                    true => c.to_ascii_lowercase(),
+                    false => c.to_ascii_uppercase(),
+                })
+                .collect()
+        };
+        let placement: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+        let side_to_move = match fields[1] {
+            "w" => "b",
+            _ => "w",
+        };
+        let mut castling: Vec<char> = swap_case(fields[2]).chars().collect();
+        castling.sort_by_key(|c| (c.is_ascii_lowercase(), *c != 'K' && *c != 'k'));
+        let castling: String = castling.into_iter().collect();
+        let en_passant = match fields[3] {
+            "-" => "-".to_string(),
+            square => square.replace('3', "x").replace('6', "3").replace('x', "6"),
+        };
+        let mirrored = [
+            placement.join("/"),
+            side_to_move.to_string(),
+            castling,
+            en_passant,
+            fields[4].to_string(),
+            fields[5].to_string(),
+        ];
+        mirrored.join(" ").parse().unwrap()
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    fn assert_symmetric(fen: &str) {
+        let board: Board = fen.parse().unwrap();
+        let mirrored = mirror(fen);
+        assert_eq!(
+            get_truncated_eval(&board),
+            -get_truncated_eval(&mirrored),
+            "{fen} and {mirrored}"
+        );
+        for piece in Piece::ALL {
+            for stage in [GameStage::MiddleGame, GameStage::EndGame] {
+                assert_eq!(
+                    get_piece_type_square_evaluation(&board, piece, Color::White, stage),
+                    get_piece_type_square_evaluation(&mirrored, piece, Color::Black, stage),
+                    "{piece:?} in {fen}"
+                );
+            }
+        }
+    }
 
     #[test]
     fn test_piece_square_evaluation_default() {
@@ -232,28 +350,116 @@ mod tests {
     #[test]
     fn test_pawn_piece_square_evaluation() {
         let board1 = Board::from_fen("3k4/8/8/8/8/5P2/8/3K4 w - - 0 1", false).unwrap();
-        assert_eq!(
-            get_pawn_piece_square_evaluation(&board1),
-            -10,
-            "failed test 1"
-        );
+        assert_eq!(pawn_evaluation(&board1), -10, "failed test 1");
         let board2 = "3k4/8/8/8/8/2P5/8/3K4 w - - 0 1".parse().unwrap();
-        assert_eq!(
-            get_pawn_piece_square_evaluation(&board2),
-            -10,
-            "failed test 2"
-        );
+        assert_eq!(pawn_evaluation(&board2), -10, "failed test 2");
         let board3 = "3k4/8/1P6/8/8/8/8/3K4 w - - 0 1".parse().unwrap();
-        assert_eq!(
-            get_pawn_piece_square_evaluation(&board3),
-            10,
-            "failed test 3"
-        );
+        assert_eq!(pawn_evaluation(&board3), 10, "failed test 3");
         let board4 = "3k4/8/2p5/8/8/8/8/3K4 w - - 0 1".parse().unwrap();
-        assert_eq!(
-            get_pawn_piece_square_evaluation(&board4),
-            10,
-            "failed test 4"
-        );
+        assert_eq!(pawn_evaluation(&board4), 10, "failed test 4");
+    }
+
+    #[test]
+    fn test_every_piece_reads_its_own_table() {
+        let board: Board = "4k3/8/8/8/8/8/1B6/Q3K2R w - - 0 1".parse().unwrap();
+        let value = |piece| {
+            get_piece_type_square_evaluation(&board, piece, Color::White, GameStage::MiddleGame)
+        };
+        assert_eq!(value(Piece::Bishop), 5);
+        assert_eq!(value(Piece::Queen), -20);
+        assert_eq!(value(Piece::Rook), 0);
+        assert_eq!(value(Piece::King), 0);
+        let castled: Board = "6k1/8/8/8/8/8/8/6K1 w - - 0 1".parse().unwrap();
+        let king = |color| {
+            get_piece_type_square_evaluation(&castled, Piece::King, color, GameStage::MiddleGame)
+        };
+        assert_eq!(king(Color::White), 30);
+        assert_eq!(king(Color::Black), 30);
+    }
+
+    #[test]
+    fn test_queen_table_is_not_mirrored_left_to_right() {
+        // The queen table is the only one that is not symmetric, favouring b3 over g3
+        let queenside: Board = "4k3/8/8/8/8/1Q6/8/4K3 w - - 0 1".parse().unwrap();
+        let kingside: Board = "4k3/8/8/8/8/6Q1/8/4K3 w - - 0 1".parse().unwrap();
+        let value = |board| {
+            get_piece_type_square_evaluation(
+                board,
+                Piece::Queen,
+                Color::White,
+                GameStage::MiddleGame,
+            )
+        };
+        assert_eq!(value(&queenside), 5);
+        assert_eq!(value(&kingside), 0);
+    }
+
+    #[test]
+    fn test_colour_flipped_positions_evaluate_to_negatives() {
+        assert_symmetric("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        assert_symmetric("4k3/8/8/8/8/5P2/8/3K4 w - - 0 1");
+        assert_symmetric("4k3/8/8/8/2N5/8/8/3K4 w - - 0 1");
+        assert_symmetric("4k3/8/8/8/8/8/1B6/4K3 w - - 0 1");
+        assert_symmetric("4k3/8/8/8/8/8/R7/4K3 w - - 0 1");
+        assert_symmetric("4k3/8/8/8/8/1Q6/8/4K3 w - - 0 1");
+        assert_symmetric("8/8/8/8/8/8/6k1/1K6 w - - 0 1");
+        assert_symmetric("8/5k2/8/3K4/8/2P5/8/8 w - - 0 40");
+        assert_symmetric("8/p4k2/8/8/8/8/4KP2/3R4 b - - 0 40");
+        assert_symmetric("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 8");
+    }
+
+    #[test]
+    fn test_game_phase() {
+        assert_eq!(game_phase(&Board::default()), MAX_PHASE);
+        let pawn_ending: Board = "8/5k2/8/3K4/8/2P5/8/8 w - - 0 40".parse().unwrap();
+        assert_eq!(game_phase(&pawn_ending), 0);
+        let rook_ending: Board = "8/p4k2/8/8/8/8/4KP2/3R4 b - - 0 40".parse().unwrap();
+        assert_eq!(game_phase(&rook_ending), 2);
+        let promoted: Board = "7k/8/8/8/8/8/QQQQQQQQ/7K b - - 0 1".parse().unwrap();
+        assert_eq!(game_phase(&promoted), MAX_PHASE);
+    }
+
+    #[test]
+    fn test_taper() {
+        assert_eq!(taper(100, -20, MAX_PHASE), 100);
+        assert_eq!(taper(100, -20, 0), -20);
+        assert_eq!(taper(100, -20, MAX_PHASE / 2), 40);
+    }
+
+    #[test]
+    fn test_kings_belong_in_the_centre_in_the_endgame() {
+        let central: Board = "8/5k2/8/8/4K3/2P5/8/8 w - - 0 40".parse().unwrap();
+        let cornered: Board = "8/5k2/8/8/8/2P5/8/6K1 w - - 0 40".parse().unwrap();
+        assert!(get_truncated_eval(&central) > get_truncated_eval(&cornered));
+        // With most pieces still on the board the king is safest castled
+        let castled: Board = "rnbq1rk1/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 w - - 0 1"
+            .parse()
+            .unwrap();
+        let central: Board = "rnbq1rk1/pppppppp/8/8/8/8/PPPPPPPP/RNBQK2R w - - 0 1"
+            .parse()
+            .unwrap();
+        assert!(get_truncated_eval(&castled) > get_truncated_eval(&central));
+    }
+
+    #[test]
+    fn test_trace_explains_the_evaluation() {
+        let board: Board = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"
+            .parse()
+            .unwrap();
+        let trace = trace(&board);
+        assert_eq!(trace.phase, MAX_PHASE);
+        assert_eq!(trace.total, get_truncated_eval(&board));
+        assert_eq!(trace.terms.len(), Piece::ALL.len() * 2);
+        let knight_squares = trace
+            .terms
+            .iter()
+            .find(|term| term.name == "Knight squares")
+            .unwrap();
+        // Nf3 and Nb1 against Nc6 and Ng8
+        assert_eq!(knight_squares.white_middle_game, 10 - 40);
+        assert_eq!(knight_squares.black_middle_game, 10 - 40);
+        // With every piece on the board the terms add up exactly
+        let sum: i32 = trace.terms.iter().map(|term| term.total(trace.phase)).sum();
+        assert_eq!(sum, trace.total);
     }
 }
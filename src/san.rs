@@ -0,0 +1,370 @@
+use crate::variant::VariantBoard;
+use cozy_chess::{Color, File, GameStatus, Move, Piece, Rank, Square};
+use std::fmt;
+
+/// The reasons a move written in SAN could not be read
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SanError {
+    /// The text holds nothing but whitespace and annotations
+    Empty,
+    /// The text is not written the way SAN writes moves
+    Invalid(String),
+    /// No legal move in the position is written this way
+    Illegal(String),
+    /// More than one legal move is written this way
+    Ambiguous(String),
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SanError::Empty => write!(f, "Empty move"),
+            SanError::Invalid(text) => write!(f, "Move [{text}] is not written in SAN"),
+            SanError::Illegal(text) => write!(f, "Illegal move [{text}]"),
+            SanError::Ambiguous(text) => write!(f, "Ambiguous move [{text}]"),
+        }
+    }
+}
+
+/// Writes a legal move in Standard Algebraic Notation
+pub fn encode(board: &VariantBoard, played_move: Move) -> String {
+    let piece = board.piece_on(played_move.from).unwrap();
+    if is_castle(board, played_move) {
+        let castle = match played_move.to.file() > played_move.from.file() {
+            true => "O-O",
+            false => "O-O-O",
+        };
+        return format!("{castle}{}", check_suffix(board, played_move));
+    }
+
+    let is_capture = board.color_on(played_move.to) == Some(!board.side_to_move())
+        || (piece == Piece::Pawn && played_move.from.file() != played_move.to.file());
+    let mut san = String::new();
+    if piece == Piece::Pawn {
+        if is_capture {
+            san.push(played_move.from.file().into());
+        }
+    } else {
+        san.push(char::from(piece).to_ascii_uppercase());
+        san.push_str(&disambiguation(board, played_move, piece));
+    }
+    if is_capture {
+        san.push('x');
+    }
+    san.push_str(&played_move.to.to_string());
+    if let Some(promotion) = played_move.promotion {
+        san.push('=');
+        san.push(char::from(promotion).to_ascii_uppercase());
+    }
+    san.push_str(check_suffix(board, played_move));
+    san
+}
+
+/// Writes a line of moves played from the position in SAN with move numbers,
+/// as in "12... Qxd5 13. Nf3"
+pub fn encode_line(board: &VariantBoard, line: &[Move]) -> String {
+    let mut board = board.clone();
+    let mut tokens = Vec::new();
+    for (index, played_move) in line.iter().enumerate() {
+        let move_number = board.fullmove_number();
+        match board.side_to_move() {
+            Color::White => tokens.push(format!("{move_number}.")),
+            Color::Black if index == 0 => tokens.push(format!("{move_number}...")),
+            Color::Black => (),
+        }
+        tokens.push(encode(&board, *played_move));
+        board.play(*played_move);
+    }
+    tokens.join(" ")
+}
+
+/// Finds the legal move written in SAN. Check marks, annotations such as !?
+/// and e.p. are ignored, castling may be written with zeros, and the = in front
+/// of a promotion may be left out
+pub fn decode(board: &VariantBoard, text: &str) -> Result<Move, SanError> {
+    let san = text
+        .trim()
+        .trim_end_matches("e.p.")
+        .trim_end()
+        .trim_end_matches(['+', '#', '!', '?']);
+    if san.is_empty() {
+        return Err(SanError::Empty);
+    }
+    let legal_moves = board.legal_moves(false);
+    let matches: Vec<Move> = match san {
+        "O-O" | "0-0" | "O-O-O" | "0-0-0" => {
+            let short = san.len() == 3;
+            legal_moves
+                .into_iter()
+                .filter(|legal_move| is_castle(board, *legal_move))
+                .filter(|legal_move| (legal_move.to.file() > legal_move.from.file()) == short)
+                .collect()
+        }
+        _ => {
+            let pattern = MovePattern::parse(san)
+                .ok_or_else(|| SanError::Invalid(text.trim().to_string()))?;
+            legal_moves
+                .into_iter()
+                .filter(|legal_move| pattern.matches(board, *legal_move))
+                .collect()
+        }
+    };
+    match matches.as_slice() {
+        [found] => Ok(*found),
+        [] => Err(SanError::Illegal(text.trim().to_string())),
+        _ => Err(SanError::Ambiguous(text.trim().to_string())),
+    }
+}
+
+/// The parts of a move written in SAN, other than castling
+struct MovePattern {
+    piece: Piece,
+    from_file: Option<File>,
+    from_rank: Option<Rank>,
+    to: Square,
+    promotion: Option<Piece>,
+}
+
+impl MovePattern {
+    /// Reads [piece][file][rank][x]square[=promotion], returning None if anything is left over
+    fn parse(san: &str) -> Option<MovePattern> {
+        let mut chars: Vec<char> = san.chars().collect();
+        let piece = match chars.first() {
+            Some(symbol) if symbol.is_ascii_uppercase() => {
+                let piece = Piece::try_from(symbol.to_ascii_lowercase()).ok()?;
+                chars.remove(0);
+                piece
+            }
+            _ => Piece::Pawn,
+        };
+        let promotion = match chars.last() {
+            Some(symbol) if symbol.is_ascii_uppercase() => {
+                let promotion = Piece::try_from(symbol.to_ascii_lowercase()).ok()?;
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+                Some(promotion)
+            }
+            _ => None,
+        };
+        let [rest @ .., to_file, to_rank] = chars.as_slice() else {
+            return None;
+        };
+        let to = Square::new(
+            File::try_from(*to_file).ok()?,
+            Rank::try_from(*to_rank).ok()?,
+        );
+        let rest = match rest {
+            [rest @ .., 'x' | ':'] => rest,
+            rest => rest,
+        };
+        let (from_file, from_rank) = match rest {
+            [] => (None, None),
+            [symbol] => match File::try_from(*symbol) {
+                Ok(file) => (Some(file), None),
+                Err(_) => (None, Some(Rank::try_from(*symbol).ok()?)),
+            },
+            [file, rank] => (
+                Some(File::try_from(*file).ok()?),
+                Some(Rank::try_from(*rank).ok()?),
+            ),
+            _ => return None,
+        };
+        Some(MovePattern {
+            piece,
+            from_file,
+            from_rank,
+            to,
+            promotion,
+        })
+    }
+
+    fn matches(&self, board: &VariantBoard, legal_move: Move) -> bool {
+        legal_move.to == self.to
+            && legal_move.promotion == self.promotion
+            && board.piece_on(legal_move.from) == Some(self.piece)
+            && self
+                .from_file
+                .is_none_or(|file| legal_move.from.file() == file)
+            && self
+                .from_rank
+                .is_none_or(|rank| legal_move.from.rank() == rank)
+            && !is_castle(board, legal_move)
+    }
+}
+
+/// cozy-chess plays castling as the king capturing its own rook
+fn is_castle(board: &VariantBoard, played_move: Move) -> bool {
+    matches!(board, VariantBoard::Standard(_))
+        && board.piece_on(played_move.from) == Some(Piece::King)
+        && board.color_on(played_move.to) == Some(board.side_to_move())
+}
+
+/// Returns the file, rank or square of the moving piece needed to tell the move
+/// apart from moves of other pieces of the same kind to the same square
+fn disambiguation(board: &VariantBoard, played_move: Move, piece: Piece) -> String {
+    let others: Vec<Move> = board
+        .legal_moves(false)
+        .into_iter()
+        .filter(|other| {
+            other.to == played_move.to
+                && other.from != played_move.from
+                && board.piece_on(other.from) == Some(piece)
+                && !is_castle(board, *other)
+        })
+        .collect();
+    if others.is_empty() {
+        return String::new();
+    }
+    let (file, rank) = (played_move.from.file(), played_move.from.rank());
+    if others.iter().all(|other| other.from.file() != file) {
+        char::from(file).to_string()
+    } else if others.iter().all(|other| other.from.rank() != rank) {
+        char::from(rank).to_string()
+    } else {
+        played_move.from.to_string()
+    }
+}
+
+/// Returns # if the move mates, + if it checks, and nothing otherwise.
+/// Checks mean nothing in antichess, so they are never marked there
+fn check_suffix(board: &VariantBoard, played_move: Move) -> &'static str {
+    let VariantBoard::Standard(board) = board else {
+        return "";
+    };
+    let mut board = board.clone();
+    board.play(played_move);
+    if board.checkers().is_empty() {
+        ""
+    } else if board.status() == GameStatus::Won {
+        "#"
+    } else {
+        "+"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antichess::parse_move;
+    use crate::variant::Variant;
+
+    fn standard(fen: &str) -> VariantBoard {
+        VariantBoard::Standard(fen.parse().unwrap())
+    }
+
+    fn san_of(board: &VariantBoard, uci_move: &str) -> String {
+        encode(board, parse_move(uci_move).unwrap())
+    }
+
+    #[test]
+    fn test_encode() {
+        let board = VariantBoard::startpos(Variant::Standard);
+        assert_eq!(san_of(&board, "g1f3"), "Nf3");
+        assert_eq!(san_of(&board, "e2e4"), "e4");
+        let castling = standard("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert_eq!(san_of(&castling, "e1h1"), "O-O");
+        assert_eq!(san_of(&castling, "e1a1"), "O-O-O");
+        let promotion = standard("1n5k/P7/8/8/8/8/8/K7 w - - 0 1");
+        assert_eq!(san_of(&promotion, "a7b8q"), "axb8=Q+");
+        let knights = standard("7k/8/8/8/8/8/8/KN3N2 w - - 0 1");
+        assert_eq!(san_of(&knights, "b1d2"), "Nbd2");
+        let rooks = standard("R7/8/7k/8/8/8/8/R5K1 w - - 0 1");
+        assert_eq!(san_of(&rooks, "a1a4"), "R1a4");
+        let queens = standard("7k/8/8/8/Q1Q5/8/Q7/K7 w - - 0 1");
+        assert_eq!(san_of(&queens, "a4b3"), "Qa4b3");
+        let mate = standard("7k/8/6K1/8/8/8/8/R7 w - - 0 1");
+        assert_eq!(san_of(&mate, "a1a8"), "Ra8#");
+        let antichess = VariantBoard::from_fen("8/P7/8/8/8/8/8/k7 w - - 0 1", Variant::Antichess);
+        assert_eq!(san_of(&antichess.unwrap(), "a7a8k"), "a8=K");
+    }
+
+    #[test]
+    fn test_encode_line() {
+        let board = VariantBoard::startpos(Variant::Standard);
+        let line: Vec<Move> = ["f2f3", "e7e5", "g2g4", "d8h4"]
+            .iter()
+            .map(|uci_move| uci_move.parse().unwrap())
+            .collect();
+        assert_eq!(encode_line(&board, &line), "1. f3 e5 2. g4 Qh4#");
+        let black = standard("4k3/8/8/8/8/8/8/4K3 b - - 0 12");
+        let line = [parse_move("e8d8").unwrap(), parse_move("e1e2").unwrap()];
+        assert_eq!(encode_line(&black, &line), "12... Kd8 13. Ke2");
+    }
+
+    #[test]
+    fn test_decode() {
+        let board = VariantBoard::startpos(Variant::Standard);
+        assert_eq!(decode(&board, "Nf3"), Ok(parse_move("g1f3").unwrap()));
+        assert_eq!(decode(&board, "e4!?"), Ok(parse_move("e2e4").unwrap()));
+        let castling = standard("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert_eq!(decode(&castling, "0-0"), Ok(parse_move("e1h1").unwrap()));
+        assert_eq!(decode(&castling, "O-O-O+"), Ok(parse_move("e1a1").unwrap()));
+        assert_eq!(
+            decode(&castling, "Kxh1"),
+            Err(SanError::Illegal("Kxh1".to_string()))
+        );
+        let promotion = standard("1n5k/P7/8/8/8/8/8/K7 w - - 0 1");
+        assert_eq!(
+            decode(&promotion, "axb8=N"),
+            Ok(parse_move("a7b8n").unwrap())
+        );
+        assert_eq!(
+            decode(&promotion, "axb8Q+"),
+            Ok(parse_move("a7b8q").unwrap())
+        );
+        let queens = standard("7k/8/8/8/Q1Q5/8/Q7/K7 w - - 0 1");
+        assert_eq!(decode(&queens, "Qa4b3"), Ok(parse_move("a4b3").unwrap()));
+        assert_eq!(decode(&queens, "Qcb3"), Ok(parse_move("c4b3").unwrap()));
+        let en_passant = standard("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2");
+        assert_eq!(
+            decode(&en_passant, "exd6 e.p."),
+            Ok(parse_move("e5d6").unwrap())
+        );
+        let antichess = VariantBoard::from_fen("8/P7/8/8/8/8/8/k7 w - - 0 1", Variant::Antichess);
+        assert_eq!(
+            decode(&antichess.unwrap(), "a8=K"),
+            Ok(parse_move("a7a8k").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let board = VariantBoard::startpos(Variant::Standard);
+        assert_eq!(decode(&board, " +"), Err(SanError::Empty));
+        assert_eq!(
+            decode(&board, "Nf9"),
+            Err(SanError::Invalid("Nf9".to_string()))
+        );
+        assert_eq!(
+            decode(&board, "Xe4"),
+            Err(SanError::Invalid("Xe4".to_string()))
+        );
+        assert_eq!(
+            decode(&board, "e5"),
+            Err(SanError::Illegal("e5".to_string()))
+        );
+        let knights = standard("7k/8/8/8/8/8/8/KN3N2 w - - 0 1");
+        assert_eq!(
+            decode(&knights, "Nd2"),
+            Err(SanError::Ambiguous("Nd2".to_string()))
+        );
+        assert_eq!(decode(&knights, "Nfd2"), Ok(parse_move("f1d2").unwrap()));
+    }
+
+    #[test]
+    fn test_every_legal_move_round_trips() {
+        let boards = [
+            standard("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"),
+            standard("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1"),
+            VariantBoard::startpos(Variant::Antichess),
+        ];
+        for board in boards {
+            for legal_move in board.legal_moves(false) {
+                let san = encode(&board, legal_move);
+                assert_eq!(decode(&board, &san), Ok(legal_move), "{san}");
+            }
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use crate::game_runner::{
+    parse_limits, parse_number, parse_option, play_game, GameSettings, Player, Score, TimeControl,
+};
+use crate::pgn::result_token;
+use crate::time_management::SearchLimits;
+use crate::uci_command::PositionStart;
+use crate::variant::Variant;
+use cozy_chess::Color;
+use std::fs::File;
+use std::io::{self, Write};
+
+/// The number of plies after which an unfinished game is adjudicated a draw
+pub const DEFAULT_MAX_PLIES: usize = 400;
+
+/// How one of the engines of a self-play match is set up
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlayerConfig {
+    pub name: String,
+    /// UCI options set before the first game, as names and values
+    pub options: Vec<(String, Option<String>)>,
+    /// The limits of every search
+    pub limits: SearchLimits,
+}
+
+/// The settings of a self-play match
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelfPlayConfig {
+    pub games: u32,
+    pub variant: Variant,
+    pub time_control: Option<TimeControl>,
+    /// Games still going after this many plies are drawn
+    pub max_plies: usize,
+    /// The number of random plies played before the engines take over, so that
+    /// games between engines that search the same way are not all the same
+    pub opening_plies: usize,
+    /// Where the games are written, or None to write them to standard output
+    pub pgn_path: Option<String>,
+    pub players: [PlayerConfig; 2],
+}
+
+impl Default for SelfPlayConfig {
+    fn default() -> Self {
+        SelfPlayConfig {
+            games: 2,
+            variant: Variant::Standard,
+            time_control: None,
+            max_plies: DEFAULT_MAX_PLIES,
+            opening_plies: 0,
+            pgn_path: None,
+            players: [
+                PlayerConfig {
+                    name: "wheatleybot A".to_string(),
+                    ..PlayerConfig::default()
+                },
+                PlayerConfig {
+                    name: "wheatleybot B".to_string(),
+                    ..PlayerConfig::default()
+                },
+            ],
+        }
+    }
+}
+
+/// Reads the arguments given after selfplay:
+/// --games N, --variant NAME, --tc BASE+INC, --max-plies N, --opening-plies N,
+/// --pgn FILE, and --name-a NAME, --option-a NAME=VALUE and --go-a LIMITS for the
+/// first engine, with the same ending in -b for the second.
+/// LIMITS are written as for the go command
+pub fn parse_args(args: &[String]) -> Result<SelfPlayConfig, String> {
+    let mut config = SelfPlayConfig::default();
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("{flag} requires a value"))
+        };
+        match flag.as_str() {
+            "--games" => config.games = parse_number(flag, value()?)?,
+            "--variant" => config.variant = value()?.parse()?,
+            "--tc" => config.time_control = Some(value()?.parse()?),
+            "--max-plies" => config.max_plies = parse_number(flag, value()?)?,
+            "--opening-plies" => config.opening_plies = parse_number(flag, value()?)?,
+            "--pgn" => config.pgn_path = Some(value()?.clone()),
+            "--name-a" => config.players[0].name = value()?.clone(),
+            "--name-b" => config.players[1].name = value()?.clone(),
+            "--option-a" => config.players[0].options.push(parse_option(value()?)),
+            "--option-b" => config.players[1].options.push(parse_option(value()?)),
+            "--go-a" => config.players[0].limits = parse_limits(value()?),
+            "--go-b" => config.players[1].limits = parse_limits(value()?),
+            _ => return Err(format!("Unknown argument [{flag}]")),
+        }
+    }
+    Ok(config)
+}
+
+/// Plays the match, writing every game as PGN, and returns the score of the first player.
+/// The players swap colours after every game
+pub fn run(config: &SelfPlayConfig) -> Result<Score, String> {
+    let player = |config: &PlayerConfig, variant| {
+        Player::wheatley(
+            &config.name,
+            &config.options,
+            config.limits.clone(),
+            variant,
+        )
+    };
+    let mut players = [
+        player(&config.players[0], config.variant)?,
+        player(&config.players[1], config.variant)?,
+    ];
+    let mut output: Box<dyn Write> = match &config.pgn_path {
+        Some(path) => Box::new(
+            File::create(path).map_err(|error| format!("Can not create [{path}]: {error}"))?,
+        ),
+        None => Box::new(io::stdout()),
+    };
+    let mut score = Score::default();
+    for round in 1..=config.games {
+        let settings = GameSettings {
+            event: "wheatleybot self-play".to_string(),
+            round,
+            variant: config.variant,
+            start: PositionStart::StartPos,
+            time_control: config.time_control,
+            max_plies: config.max_plies,
+            opening_plies: config.opening_plies,
+        };
+        let first_color = match round % 2 {
+            1 => Color::White,
+            _ => Color::Black,
+        };
+        let [first, second] = &mut players;
+        let (game, outcome) = match first_color {
+            Color::White => play_game(first, second, &settings)?,
+            Color::Black => play_game(second, first, &settings)?,
+        };
+        score.add(outcome, first_color);
+        writeln!(output, "{game}").map_err(|error| format!("Can not write the game: {error}"))?;
+        if config.pgn_path.is_some() {
+            println!(
+                "Game {round}: {} - {} {}",
+                game.tag("White").unwrap_or("?"),
+                game.tag("Black").unwrap_or("?"),
+                result_token(Some(outcome))
+            );
+        }
+    }
+    Ok(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let mut arguments = args("--games 4 --variant antichess --option-b ForcedCapture=false");
+        arguments.extend(["--go-a".to_string(), "depth 2".to_string()]);
+        let config = parse_args(&arguments).unwrap();
+        assert_eq!(config.games, 4);
+        assert_eq!(config.variant, Variant::Antichess);
+        assert_eq!(config.players[0].limits.depth, Some(2));
+        assert_eq!(
+            config.players[1].options,
+            vec![("ForcedCapture".to_string(), Some("false".to_string()))]
+        );
+        assert!(parse_args(&args("--games many")).is_err());
+        assert!(parse_args(&args("--pgn")).is_err());
+        assert!(parse_args(&args("--tc 1+x")).is_err());
+    }
+
+    #[test]
+    fn test_games_are_played_to_a_result() {
+        let pgn_path =
+            std::env::temp_dir().join(format!("wheatley_selfplay_{}.pgn", std::process::id()));
+        let mut config = SelfPlayConfig {
+            games: 2,
+            variant: Variant::Antichess,
+            opening_plies: 2,
+            pgn_path: Some(pgn_path.display().to_string()),
+            ..SelfPlayConfig::default()
+        };
+        for player in &mut config.players {
+            player.limits = parse_limits("depth 1");
+        }
+        let score = run(&config).unwrap();
+        assert_eq!(score.wins + score.draws + score.losses, 2);
+        let pgn = std::fs::read_to_string(&pgn_path).unwrap();
+        std::fs::remove_file(pgn_path).unwrap();
+        assert_eq!(pgn.matches("[Variant \"Antichess\"]").count(), 2);
+        assert!(pgn.contains("[Round \"2\"]\n[White \"wheatleybot B\"]"));
+    }
+
+    #[test]
+    fn test_invalid_options_stop_the_match() {
+        let mut config = SelfPlayConfig::default();
+        config.players[1].options.push(parse_option("Hash=lots"));
+        assert!(run(&config).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use cozy_chess::Color;
+use std::time::Duration;
+
+/// Time kept in reserve for communication with the GUI
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+/// The number of moves the remaining time is split over when the GUI does not send movestogo
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+/// How many times the soft budget the search may use before it is aborted
+const HARD_BUDGET_FACTOR: u32 = 3;
+
+/// The parameters of the go command that limit a search
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    /// Time white has left on the clock
+    pub white_time: Option<Duration>,
+    /// Time black has left on the clock
+    pub black_time: Option<Duration>,
+    /// White increment per move
+    pub white_increment: Option<Duration>,
+    /// Black increment per move
+    pub black_increment: Option<Duration>,
+    /// Moves until the next time control
+    pub moves_to_go: Option<u32>,
+    /// Search exactly this long
+    pub move_time: Option<Duration>,
+    /// Search no deeper than this many iterations
+    pub depth: Option<i32>,
+    /// Search no more than this many nodes
+    pub nodes: Option<u64>,
+    /// Keep searching until the stop command is received
+    pub infinite: bool,
+}
+
+/// The amount of time a single search may use
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeBudget {
+    /// Once this has elapsed no new iteration is started
+    pub soft: Duration,
+    /// Once this has elapsed the search is aborted
+    pub hard: Duration,
+}
+
+/// Parses the tokens of the go command into search limits.
+/// Unknown tokens and malformed values are ignored
+pub fn parse_go(input_tokens: &[&str]) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    let mut tokens = input_tokens.iter().skip(1);
+    while let Some(token) = tokens.next() {
+        match *token {
+            "wtime" => limits.white_time = parse_millis(tokens.next()),
+            "btime" => limits.black_time = parse_millis(tokens.next()),
+            "winc" => limits.white_increment = parse_millis(tokens.next()),
+            "binc" => limits.black_increment = parse_millis(tokens.next()),
+            "movestogo" => limits.moves_to_go = tokens.next().and_then(|value| value.parse().ok()),
+            "movetime" => limits.move_time = parse_millis(tokens.next()),
+            "depth" => limits.depth = tokens.next().and_then(|value| value.parse().ok()),
+            "nodes" => limits.nodes = tokens.next().and_then(|value| value.parse().ok()),
+            "infinite" => limits.infinite = true,
+            _ => (),
+        }
+    }
+    limits
+}
+
+/// Parses a number of milliseconds. Some GUIs send negative times when a
+/// player has overstepped, these are treated as no time left
+fn parse_millis(token: Option<&&str>) -> Option<Duration> {
+    let millis: i64 = token?.parse().ok()?;
+    Some(Duration::from_millis(millis.max(0) as u64))
+}
+
+impl SearchLimits {
+    /// Returns how long the side to move may search, or None if the search is not timed
+    pub fn time_budget(&self, side_to_move: Color) -> Option<TimeBudget> {
+        if let Some(move_time) = self.move_time {
+            let budget = move_time.saturating_sub(MOVE_OVERHEAD);
+            return Some(TimeBudget {
+                soft: budget,
+                hard: budget,
+            });
+        }
+        let (remaining, increment) = match side_to_move {
+            Color::White => (self.white_time?, self.white_increment),
+            Color::Black => (self.black_time?, self.black_increment),
+        };
+        let available = remaining.saturating_sub(MOVE_OVERHEAD);
+        let moves_to_go = self.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let increment = increment.unwrap_or_default();
+        let hard = available / 2;
+        let soft = (available / moves_to_go + increment * 3 / 4).min(hard);
+        Some(TimeBudget {
+            soft,
+            hard: (soft * HARD_BUDGET_FACTOR).min(hard),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_go() {
+        let limits = parse_go(&[
+            "go",
+            "wtime",
+            "60000",
+            "btime",
+            "-20",
+            "winc",
+            "1000",
+            "movestogo",
+            "12",
+        ]);
+        assert_eq!(limits.white_time, Some(Duration::from_secs(60)));
+        assert_eq!(limits.black_time, Some(Duration::ZERO));
+        assert_eq!(limits.white_increment, Some(Duration::from_secs(1)));
+        assert_eq!(limits.black_increment, None);
+        assert_eq!(limits.moves_to_go, Some(12));
+        assert_eq!(limits.move_time, None);
+        assert!(!limits.infinite);
+    }
+
+    #[test]
+    fn test_parse_go_search_limits() {
+        let limits = parse_go(&["go", "depth", "5", "nodes", "20000", "infinite"]);
+        assert_eq!(limits.depth, Some(5));
+        assert_eq!(limits.nodes, Some(20000));
+        assert!(limits.infinite);
+        assert_eq!(limits.time_budget(Color::White), None);
+        let malformed = parse_go(&["go", "depth", "deep", "nodes"]);
+        assert_eq!(malformed.depth, None);
+        assert_eq!(malformed.nodes, None);
+    }
+
+    #[test]
+    fn test_untimed_search_has_no_budget() {
+        assert_eq!(parse_go(&["go"]).time_budget(Color::White), None);
+        let black_only = parse_go(&["go", "btime", "1000"]);
+        assert_eq!(black_only.time_budget(Color::White), None);
+    }
+
+    #[test]
+    fn test_move_time_budget() {
+        let budget = parse_go(&["go", "movetime", "1050"])
+            .time_budget(Color::Black)
+            .unwrap();
+        assert_eq!(budget.soft, Duration::from_millis(1000));
+        assert_eq!(budget.hard, Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_clock_budget_stays_within_remaining_time() {
+        let limits = parse_go(&["go", "wtime", "3050", "btime", "100", "binc", "2000"]);
+        let white = limits.time_budget(Color::White).unwrap();
+        assert_eq!(white.soft, Duration::from_millis(100));
+        assert_eq!(white.hard, Duration::from_millis(300));
+        let black = limits.time_budget(Color::Black).unwrap();
+        assert!(black.soft <= black.hard);
+        assert!(black.hard <= Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,146 @@
+use cozy_chess::Move;
+use std::mem::size_of;
+
+/// The size of the table in megabytes when the GUI does not set one
+pub const DEFAULT_HASH_MB: usize = 16;
+/// The largest table the GUI may ask for in megabytes
+pub const MAX_HASH_MB: usize = 1024;
+
+/// How the stored evaluation relates to the true evaluation of the position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    /// The evaluation is exact
+    Exact,
+    /// The true evaluation is at least the stored evaluation
+    Lower,
+    /// The true evaluation is at most the stored evaluation
+    Upper,
+}
+
+/// The result of searching a position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TableEntry {
+    /// The hash of the board that was searched
+    pub key: u64,
+    /// The depth the position was searched to
+    pub depth: i32,
+    /// How the evaluation relates to the true evaluation
+    pub bound: Bound,
+    /// The evaluation for white
+    pub evaluation: i32,
+    /// The move the side to move chose, if it had any
+    pub best_move: Option<Move>,
+}
+
+impl TableEntry {
+    /// Returns the evaluation if it is enough to cut off a search of the given window.
+    /// An exact evaluation from a search at least as deep always is
+    pub fn cutoff(&self, depth: i32, alpha: i32, beta: i32) -> Option<i32> {
+        if self.depth < depth {
+            return None;
+        }
+        let cutoff = match self.bound {
+            Bound::Exact => true,
+            Bound::Lower => self.evaluation >= beta,
+            Bound::Upper => self.evaluation <= alpha,
+        };
+        cutoff.then_some(self.evaluation)
+    }
+}
+
+/// A fixed size cache of search results, indexed by the hash of the board
+pub struct TranspositionTable {
+    entries: Vec<Option<TableEntry>>,
+}
+
+impl TranspositionTable {
+    /// Creates an empty table using about the given number of megabytes
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let entry_count = (size_mb * 1024 * 1024 / size_of::<Option<TableEntry>>()).max(1);
+        TranspositionTable {
+            entries: vec![None; entry_count],
+        }
+    }
+
+    /// Removes every entry from the table
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+
+    /// Returns the entry stored for the board with the given hash
+    pub fn probe(&self, key: u64) -> Option<TableEntry> {
+        self.entries[self.index(key)].filter(|entry| entry.key == key)
+    }
+
+    /// Stores a search result. An entry for a different board is always replaced,
+    /// an entry for the same board is only replaced by a search that is as deep
+    pub fn store(&mut self, entry: TableEntry) {
+        let index = self.index(entry.key);
+        let replace = match self.entries[index] {
+            Some(existing) => existing.key != entry.key || entry.depth >= existing.depth,
+            None => true,
+        };
+        if replace {
+            self.entries[index] = Some(entry);
+        }
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key % self.entries.len() as u64) as usize
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(DEFAULT_HASH_MB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: u64, depth: i32, bound: Bound, evaluation: i32) -> TableEntry {
+        TableEntry {
+            key,
+            depth,
+            bound,
+            evaluation,
+            best_move: "e2e4".parse().ok(),
+        }
+    }
+
+    #[test]
+    fn test_store_and_probe() {
+        let mut table = TranspositionTable::new(1);
+        table.store(entry(42, 3, Bound::Exact, 10));
+        assert_eq!(table.probe(42), Some(entry(42, 3, Bound::Exact, 10)));
+        assert_eq!(table.probe(43), None);
+        table.clear();
+        assert_eq!(table.probe(42), None);
+    }
+
+    #[test]
+    fn test_deeper_results_are_kept() {
+        let mut table = TranspositionTable::new(1);
+        table.store(entry(42, 3, Bound::Exact, 10));
+        table.store(entry(42, 2, Bound::Exact, 20));
+        assert_eq!(table.probe(42).unwrap().evaluation, 10);
+        table.store(entry(42, 4, Bound::Lower, 30));
+        assert_eq!(table.probe(42).unwrap().evaluation, 30);
+    }
+
+    #[test]
+    fn test_cutoff() {
+        let exact = entry(1, 3, Bound::Exact, 50);
+        assert_eq!(exact.cutoff(4, 0, 100), None);
+        assert_eq!(exact.cutoff(3, 0, 100), Some(50));
+        assert_eq!(exact.cutoff(3, 60, 100), Some(50));
+        let lower = entry(1, 3, Bound::Lower, 50);
+        assert_eq!(lower.cutoff(2, 0, 40), Some(50));
+        assert_eq!(lower.cutoff(2, 0, 60), None);
+        let upper = entry(1, 3, Bound::Upper, 50);
+        assert_eq!(upper.cutoff(2, 55, 100), Some(50));
+        assert_eq!(upper.cutoff(2, 45, 100), None);
+    }
+}
@@ -0,0 +1,325 @@
+use crate::bench::DEFAULT_BENCH_DEPTH;
+use crate::time_management::{self, SearchLimits};
+use std::fmt;
+
+/// Where the position command starts before its moves are played
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum PositionStart {
+    #[default]
+    StartPos,
+    Fen(String),
+}
+
+/// A command sent by the GUI
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UciCommand {
+    Uci,
+    /// Weither debug mode is switched on
+    Debug(bool),
+    IsReady,
+    /// The name may be several words long, as may the value
+    SetOption {
+        name: String,
+        value: Option<String>,
+    },
+    Register,
+    UciNewGame,
+    /// The moves are kept in UCI notation, as they can only be read against the board
+    Position {
+        start: PositionStart,
+        moves: Vec<String>,
+    },
+    Go(SearchLimits),
+    /// Count the positions this many moves deep, as go perft asks
+    Perft(u32),
+    /// Search the bench positions to this depth and report the speed
+    Bench(i32),
+    /// Explain the static evaluation of the current position
+    Eval,
+    Stop,
+    PonderHit,
+    Quit,
+}
+
+/// The reasons a line sent by the GUI is not a valid command
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UciParseError {
+    /// The line holds nothing but whitespace
+    Empty,
+    /// None of the tokens on the line is a command
+    UnknownCommand(String),
+    /// The command is missing something it requires
+    MissingArgument {
+        command: &'static str,
+        argument: &'static str,
+    },
+    /// The command was given a token it does not understand
+    InvalidArgument {
+        command: &'static str,
+        argument: String,
+    },
+}
+
+impl fmt::Display for UciParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UciParseError::Empty => write!(f, "Empty command"),
+            UciParseError::UnknownCommand(line) => {
+                write!(f, "UCI Command not recognized. Input string was [{line}]")
+            }
+            UciParseError::MissingArgument { command, argument } => {
+                write!(f, "The {command} command requires {argument}")
+            }
+            UciParseError::InvalidArgument { command, argument } => {
+                write!(f, "The {command} command does not accept [{argument}]")
+            }
+        }
+    }
+}
+
+/// Parses a line sent by the GUI.
+/// As the UCI protocol asks, unknown tokens in front of a command are skipped,
+/// so "joho debug on" switches debug mode on
+pub fn parse(line: &str) -> Result<UciCommand, UciParseError> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.is_empty() {
+        return Err(UciParseError::Empty);
+    }
+    for (index, token) in tokens.iter().enumerate() {
+        let arguments = &tokens[index + 1..];
+        let command = match *token {
+            "uci" => UciCommand::Uci,
+            "debug" => parse_debug(arguments)?,
+            "isready" => UciCommand::IsReady,
+            "setoption" => parse_set_option(arguments)?,
+            "register" => UciCommand::Register,
+            "ucinewgame" => UciCommand::UciNewGame,
+            "position" => parse_position(arguments)?,
+            "go" if arguments.first() == Some(&"perft") => parse_perft(&arguments[1..])?,
+            "go" => UciCommand::Go(time_management::parse_go(&tokens[index..])),
+            "stop" => UciCommand::Stop,
+            "ponderhit" => UciCommand::PonderHit,
+            "quit" => UciCommand::Quit,
+            "bench" => parse_bench(arguments)?,
+            "eval" => UciCommand::Eval,
+            _ => continue,
+        };
+        return Ok(command);
+    }
+    Err(UciParseError::UnknownCommand(tokens.join(" ")))
+}
+
+fn parse_debug(arguments: &[&str]) -> Result<UciCommand, UciParseError> {
+    match arguments.first() {
+        Some(&"on") => Ok(UciCommand::Debug(true)),
+        Some(&"off") => Ok(UciCommand::Debug(false)),
+        Some(argument) => Err(UciParseError::InvalidArgument {
+            command: "debug",
+            argument: argument.to_string(),
+        }),
+        None => Err(UciParseError::MissingArgument {
+            command: "debug",
+            argument: "on or off",
+        }),
+    }
+}
+
+/// Parses go perft <depth>
+fn parse_perft(arguments: &[&str]) -> Result<UciCommand, UciParseError> {
+    match arguments.first() {
+        Some(depth) => {
+            depth
+                .parse()
+                .map(UciCommand::Perft)
+                .map_err(|_| UciParseError::InvalidArgument {
+                    command: "go perft",
+                    argument: depth.to_string(),
+                })
+        }
+        None => Err(UciParseError::MissingArgument {
+            command: "go perft",
+            argument: "a depth",
+        }),
+    }
+}
+
+/// Parses bench [depth]
+fn parse_bench(arguments: &[&str]) -> Result<UciCommand, UciParseError> {
+    match arguments.first() {
+        Some(depth) => match depth.parse() {
+            Ok(depth) if depth > 0 => Ok(UciCommand::Bench(depth)),
+            _ => Err(UciParseError::InvalidArgument {
+                command: "bench",
+                argument: depth.to_string(),
+            }),
+        },
+        None => Ok(UciCommand::Bench(DEFAULT_BENCH_DEPTH)),
+    }
+}
+
+/// Parses setoption name <id> [value <x>]
+fn parse_set_option(arguments: &[&str]) -> Result<UciCommand, UciParseError> {
+    let missing_name = UciParseError::MissingArgument {
+        command: "setoption",
+        argument: "a name",
+    };
+    let arguments = match arguments.split_first() {
+        Some((&"name", arguments)) => arguments,
+        _ => return Err(missing_name),
+    };
+    let value_index = arguments.iter().position(|token| *token == "value");
+    let name = arguments[..value_index.unwrap_or(arguments.len())].join(" ");
+    if name.is_empty() {
+        return Err(missing_name);
+    }
+    let value = value_index.map(|index| arguments[index + 1..].join(" "));
+    Ok(UciCommand::SetOption { name, value })
+}
+
+/// Parses position [fen <fenstring> | startpos] [moves <move1> ... <movei>]
+fn parse_position(arguments: &[&str]) -> Result<UciCommand, UciParseError> {
+    let (start, rest) = match arguments.split_first() {
+        Some((&"startpos", rest)) => (PositionStart::StartPos, rest),
+        Some((&"fen", rest)) => {
+            let fen_end = rest
+                .iter()
+                .position(|token| *token == "moves")
+                .unwrap_or(rest.len());
+            if fen_end == 0 {
+                return Err(UciParseError::MissingArgument {
+                    command: "position",
+                    argument: "a FEN",
+                });
+            }
+            let fen = fen_from_tokens(&rest[..fen_end]);
+            (PositionStart::Fen(fen), &rest[fen_end..])
+        }
+        Some((argument, _)) => {
+            return Err(UciParseError::InvalidArgument {
+                command: "position",
+                argument: argument.to_string(),
+            })
+        }
+        None => {
+            return Err(UciParseError::MissingArgument {
+                command: "position",
+                argument: "a starting point of startpos or fen",
+            })
+        }
+    };
+    let moves = match rest.split_first() {
+        Some((&"moves", moves)) => moves.iter().map(|token| token.to_string()).collect(),
+        Some((argument, _)) => {
+            return Err(UciParseError::InvalidArgument {
+                command: "position",
+                argument: argument.to_string(),
+            })
+        }
+        None => Vec::new(),
+    };
+    Ok(UciCommand::Position { start, moves })
+}
+
+/// Joins the fields of a FEN sent as separate tokens.
+/// The halfmove clock and fullmove number are optional in some GUIs, so they
+/// are filled in with their starting values when missing
+fn fen_from_tokens(fen_tokens: &[&str]) -> String {
+    let mut fields = fen_tokens.to_vec();
+    if fields.len() == 4 {
+        fields.push("0");
+    }
+    if fields.len() == 5 {
+        fields.push("1");
+    }
+    fields.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blank_lines_are_empty() {
+        assert_eq!(parse(""), Err(UciParseError::Empty));
+        assert_eq!(parse("  \r\n"), Err(UciParseError::Empty));
+    }
+
+    #[test]
+    fn test_unknown_tokens_are_skipped() {
+        assert_eq!(parse("joho debug on\n"), Ok(UciCommand::Debug(true)));
+        assert_eq!(parse("isready\r\n"), Ok(UciCommand::IsReady));
+        assert_eq!(
+            parse("hello there"),
+            Err(UciParseError::UnknownCommand("hello there".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_debug_requires_on_or_off() {
+        assert_eq!(parse("debug off"), Ok(UciCommand::Debug(false)));
+        assert!(parse("debug").is_err());
+        assert!(parse("debug maybe").is_err());
+    }
+
+    #[test]
+    fn test_set_option() {
+        assert_eq!(
+            parse("setoption name Clear Hash"),
+            Ok(UciCommand::SetOption {
+                name: "Clear Hash".to_string(),
+                value: None
+            })
+        );
+        assert_eq!(
+            parse("setoption name Book File value /home/my book.bin"),
+            Ok(UciCommand::SetOption {
+                name: "Book File".to_string(),
+                value: Some("/home/my book.bin".to_string())
+            })
+        );
+        assert!(parse("setoption Hash value 4").is_err());
+        assert!(parse("setoption name value 4").is_err());
+    }
+
+    #[test]
+    fn test_position() {
+        assert_eq!(
+            parse("position startpos moves e2e4 e7e5"),
+            Ok(UciCommand::Position {
+                start: PositionStart::StartPos,
+                moves: vec!["e2e4".to_string(), "e7e5".to_string()]
+            })
+        );
+        assert_eq!(
+            parse("position fen 4k3/8/8/8/8/8/8/4K3 b - -"),
+            Ok(UciCommand::Position {
+                start: PositionStart::Fen("4k3/8/8/8/8/8/8/4K3 b - - 0 1".to_string()),
+                moves: Vec::new()
+            })
+        );
+        assert!(parse("position").is_err());
+        assert!(parse("position fen moves e2e4").is_err());
+        assert!(parse("position startpos e2e4").is_err());
+        assert!(parse("position somewhere").is_err());
+    }
+
+    #[test]
+    fn test_go() {
+        let Ok(UciCommand::Go(limits)) = parse("go depth 4 infinite") else {
+            panic!("go was not parsed");
+        };
+        assert_eq!(limits.depth, Some(4));
+        assert!(limits.infinite);
+        assert_eq!(parse("go perft 5"), Ok(UciCommand::Perft(5)));
+        assert!(parse("go perft").is_err());
+        assert!(parse("go perft deep").is_err());
+    }
+
+    #[test]
+    fn test_bench() {
+        assert_eq!(parse("bench"), Ok(UciCommand::Bench(DEFAULT_BENCH_DEPTH)));
+        assert_eq!(parse("bench 3"), Ok(UciCommand::Bench(3)));
+        assert!(parse("bench -1").is_err());
+        assert_eq!(parse("eval"), Ok(UciCommand::Eval));
+    }
+}
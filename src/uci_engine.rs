@@ -0,0 +1,271 @@
+use crate::time_management::SearchLimits;
+use crate::uci_command::PositionStart;
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long an engine may take to answer uci and isready
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long an engine that is told to quit has before it is killed
+const QUIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The ways talking to an external engine can fail
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UciEngineError {
+    /// The engine did not answer in time
+    Timeout,
+    /// The engine closed its output, usually because it crashed
+    Closed,
+    /// The engine could not be started or written to
+    Io(String),
+}
+
+impl fmt::Display for UciEngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UciEngineError::Timeout => write!(f, "The engine did not answer in time"),
+            UciEngineError::Closed => write!(f, "The engine closed its output"),
+            UciEngineError::Io(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+/// A UCI engine running as a child process
+pub struct UciEngine {
+    child: Child,
+    stdin: ChildStdin,
+    /// The lines the engine writes, read on their own thread so reading can time out
+    lines: Receiver<String>,
+    /// The name the engine gave in its id
+    name: String,
+}
+
+impl UciEngine {
+    /// Starts the engine and waits until it has said uciok
+    pub fn start(command: &str, args: &[String]) -> Result<UciEngine, UciEngineError> {
+        let mut child = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|error| UciEngineError::Io(format!("Can not start [{command}]: {error}")))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut engine = UciEngine {
+            child,
+            stdin,
+            lines,
+            name: command.to_string(),
+        };
+        engine.send("uci")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            let line = engine.read_line(Some(deadline))?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.trim().to_string();
+            }
+            if line.trim() == "uciok" {
+                return Ok(engine);
+            }
+        }
+    }
+
+    /// The name the engine gave, or the command that started it if it gave none
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), UciEngineError> {
+        match value {
+            Some(value) => self.send(&format!("setoption name {name} value {value}")),
+            None => self.send(&format!("setoption name {name}")),
+        }
+    }
+
+    /// Tells the engine a new game starts and waits until it is ready for it
+    pub fn new_game(&mut self) -> Result<(), UciEngineError> {
+        self.send("ucinewgame")?;
+        self.send("isready")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        while self.read_line(Some(deadline))?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    /// Searches the position after the moves and returns the move the engine plays
+    /// in UCI notation. If timeout passes without an answer the engine is told to
+    /// stop, and the search fails
+    pub fn search(
+        &mut self,
+        start: &PositionStart,
+        moves: &[String],
+        limits: &SearchLimits,
+        timeout: Option<Duration>,
+    ) -> Result<String, UciEngineError> {
+        let mut position = match start {
+            PositionStart::StartPos => "position startpos".to_string(),
+            PositionStart::Fen(fen) => format!("position fen {fen}"),
+        };
+        if !moves.is_empty() {
+            position.push_str(" moves ");
+            position.push_str(&moves.join(" "));
+        }
+        self.send(&position)?;
+        self.send(&go_command(limits))?;
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let line = match self.read_line(deadline) {
+                Err(UciEngineError::Timeout) => {
+                    // The best move the engine sends after stop arrives too late to be played
+                    self.send("stop")?;
+                    return Err(UciEngineError::Timeout);
+                }
+                line => line?,
+            };
+            let mut tokens = line.split_whitespace();
+            if tokens.next() == Some("bestmove") {
+                return tokens
+                    .next()
+                    .map(str::to_string)
+                    .ok_or(UciEngineError::Closed);
+            }
+        }
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), UciEngineError> {
+        writeln!(self.stdin, "{command}")
+            .and_then(|_| self.stdin.flush())
+            .map_err(|error| UciEngineError::Io(format!("Can not write to the engine: {error}")))
+    }
+
+    /// Waits for the next line the engine writes, giving up at the deadline
+    fn read_line(&mut self, deadline: Option<Instant>) -> Result<String, UciEngineError> {
+        match deadline {
+            Some(deadline) => {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                self.lines
+                    .recv_timeout(timeout)
+                    .map_err(|error| match error {
+                        RecvTimeoutError::Timeout => UciEngineError::Timeout,
+                        RecvTimeoutError::Disconnected => UciEngineError::Closed,
+                    })
+            }
+            None => self.lines.recv().map_err(|_| UciEngineError::Closed),
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    /// Asks the engine to quit, and kills it if it does not
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + QUIT_TIMEOUT;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Writes the search limits as a go command
+fn go_command(limits: &SearchLimits) -> String {
+    let mut command = "go".to_string();
+    let millis = |time: Duration| time.as_millis().to_string();
+    let arguments = [
+        ("wtime", limits.white_time.map(millis)),
+        ("btime", limits.black_time.map(millis)),
+        ("winc", limits.white_increment.map(millis)),
+        ("binc", limits.black_increment.map(millis)),
+        (
+            "movestogo",
+            limits.moves_to_go.map(|moves| moves.to_string()),
+        ),
+        ("movetime", limits.move_time.map(millis)),
+        ("depth", limits.depth.map(|depth| depth.to_string())),
+        ("nodes", limits.nodes.map(|nodes| nodes.to_string())),
+    ];
+    for (name, value) in arguments {
+        if let Some(value) = value {
+            command.push_str(&format!(" {name} {value}"));
+        }
+    }
+    if limits.infinite {
+        command.push_str(" infinite");
+    }
+    command
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::time_management::parse_go;
+
+    /// The arguments to sh for a script that answers the way a UCI engine would,
+    /// running on_go for every go command
+    pub(crate) fn stub_args(on_go: &str) -> Vec<String> {
+        let script = format!(
+            "while read line; do case \"$line\" in \
+             uci) echo 'id name Stub'; echo uciok;; \
+             isready) echo readyok;; \
+             go*) {on_go};; \
+             quit) exit;; \
+             esac; done"
+        );
+        vec!["-c".to_string(), script]
+    }
+
+    fn stub_engine(on_go: &str) -> UciEngine {
+        UciEngine::start("sh", &stub_args(on_go)).unwrap()
+    }
+
+    #[test]
+    fn test_go_command_round_trips() {
+        let tokens = [
+            "go", "wtime", "1000", "btime", "900", "winc", "10", "depth", "3",
+        ];
+        let limits = parse_go(&tokens);
+        assert_eq!(go_command(&limits), tokens.join(" "));
+        assert_eq!(go_command(&parse_go(&["go", "infinite"])), "go infinite");
+    }
+
+    #[test]
+    fn test_search() {
+        let mut engine = stub_engine("echo 'info depth 1'; echo 'bestmove e2e4 ponder e7e5'");
+        assert_eq!(engine.name(), "Stub");
+        engine.new_game().unwrap();
+        let limits = SearchLimits::default();
+        let best_move = engine.search(&PositionStart::StartPos, &[], &limits, None);
+        assert_eq!(best_move, Ok("e2e4".to_string()));
+    }
+
+    #[test]
+    fn test_search_times_out() {
+        let mut engine = stub_engine("true");
+        let timeout = Some(Duration::from_millis(50));
+        let limits = SearchLimits::default();
+        let best_move = engine.search(&PositionStart::StartPos, &[], &limits, timeout);
+        assert_eq!(best_move, Err(UciEngineError::Timeout));
+    }
+
+    #[test]
+    fn test_missing_engine() {
+        let engine = UciEngine::start("/nonexistent/engine", &[]);
+        assert!(matches!(engine, Err(UciEngineError::Io(_))));
+    }
+}
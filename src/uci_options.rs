@@ -0,0 +1,372 @@
+use crate::engine::{DEFAULT_SEARCH_DEPTH, MAX_SEARCH_DEPTH, MAX_SKILL};
+use crate::evaluator::EvaluatorKind;
+use crate::play::Game;
+use crate::transposition_table::{TranspositionTable, DEFAULT_HASH_MB, MAX_HASH_MB};
+use crate::variant::{Variant, VariantBoard};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// The control a GUI shows for an option, along with its default value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OptionType {
+    Check {
+        default: bool,
+    },
+    Spin {
+        default: i64,
+        min: i64,
+        max: i64,
+    },
+    /// One of a fixed list of values
+    Combo {
+        default: String,
+        values: Vec<String>,
+    },
+    String {
+        default: String,
+    },
+    /// Carries out an action when pressed and has no value
+    Button,
+}
+
+/// The value of an option once it has been checked against the type of the option
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OptionValue {
+    Check(bool),
+    Spin(i64),
+    /// The value of a combo or string option. Combo values are spelt as the option declares them
+    Text(String),
+    Button,
+}
+
+impl OptionValue {
+    fn check(&self) -> bool {
+        matches!(self, OptionValue::Check(true))
+    }
+
+    fn spin(&self) -> i64 {
+        match self {
+            OptionValue::Spin(value) => *value,
+            _ => 0,
+        }
+    }
+
+    fn text(&self) -> &str {
+        match self {
+            OptionValue::Text(value) => value,
+            _ => "",
+        }
+    }
+}
+
+/// An option the engine lets the GUI set
+#[derive(Clone)]
+pub struct UciOption {
+    pub name: &'static str,
+    pub option_type: OptionType,
+    /// Sets the option on the game, the value has already been checked against the type
+    apply: fn(OptionValue, Game) -> Game,
+}
+
+impl fmt::Display for UciOption {
+    /// Writes the option the way the engine advertises it after the uci command
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "option name {} type ", self.name)?;
+        match &self.option_type {
+            OptionType::Check { default } => write!(f, "check default {default}"),
+            OptionType::Spin { default, min, max } => {
+                write!(f, "spin default {default} min {min} max {max}")
+            }
+            OptionType::Combo { default, values } => {
+                write!(f, "combo default {default}")?;
+                values
+                    .iter()
+                    .try_for_each(|value| write!(f, " var {value}"))
+            }
+            // An empty string can not be sent, so UCI writes it as <empty>
+            OptionType::String { default } if default.is_empty() => {
+                write!(f, "string default <empty>")
+            }
+            OptionType::String { default } => write!(f, "string default {default}"),
+            OptionType::Button => write!(f, "button"),
+        }
+    }
+}
+
+impl UciOption {
+    /// Checks a value sent by the GUI against the type of the option.
+    /// Names of check and combo values are not case sensitive, as UCI asks
+    pub fn parse_value(&self, value: Option<&str>) -> Result<OptionValue, String> {
+        let name = self.name;
+        let invalid = || {
+            format!(
+                "Invalid value [{}] for option {name}",
+                value.unwrap_or_default()
+            )
+        };
+        match (&self.option_type, value) {
+            (OptionType::Button, _) => Ok(OptionValue::Button),
+            (OptionType::String { .. }, Some("<empty>")) => Ok(OptionValue::Text(String::new())),
+            (OptionType::String { .. }, Some(value)) => Ok(OptionValue::Text(value.to_string())),
+            (_, None) => Err(invalid()),
+            (OptionType::Check { .. }, Some(value)) => match value.to_ascii_lowercase().as_str() {
+                "true" => Ok(OptionValue::Check(true)),
+                "false" => Ok(OptionValue::Check(false)),
+                _ => Err(invalid()),
+            },
+            (OptionType::Spin { min, max, .. }, Some(value)) => {
+                let number: i64 = value.parse().map_err(|_| invalid())?;
+                if !(*min..=*max).contains(&number) {
+                    return Err(format!(
+                        "Value [{number}] for option {name} is outside {min} to {max}"
+                    ));
+                }
+                Ok(OptionValue::Spin(number))
+            }
+            (OptionType::Combo { values, .. }, Some(value)) => values
+                .iter()
+                .find(|allowed| allowed.eq_ignore_ascii_case(value))
+                .map(|allowed| OptionValue::Text(allowed.clone()))
+                .ok_or_else(invalid),
+        }
+    }
+}
+
+/// Every option the engine supports, in the order they are advertised.
+/// This is the only place an option needs to be declared
+pub fn options() -> Vec<UciOption> {
+    vec![
+        UciOption {
+            name: "ForcedCapture",
+            option_type: OptionType::Check { default: true },
+            apply: |value, game| {
+                // Scores stored with the other set of legal moves would mislead the search
+                game.transposition_table.lock().unwrap().clear();
+                Game {
+                    forced_capture: value.check(),
+                    ..game
+                }
+            },
+        },
+        UciOption {
+            name: "Depth",
+            option_type: OptionType::Spin {
+                default: DEFAULT_SEARCH_DEPTH.into(),
+                min: 1,
+                max: MAX_SEARCH_DEPTH.into(),
+            },
+            apply: |value, game| Game {
+                depth: value.spin() as i32,
+                ..game
+            },
+        },
+        UciOption {
+            name: "Skill Level",
+            option_type: OptionType::Spin {
+                default: MAX_SKILL.into(),
+                min: 0,
+                max: MAX_SKILL.into(),
+            },
+            apply: |value, game| Game {
+                skill: value.spin() as i32,
+                ..game
+            },
+        },
+        UciOption {
+            name: "Evaluator",
+            option_type: OptionType::Combo {
+                default: EvaluatorKind::default().to_string(),
+                values: EvaluatorKind::ALL
+                    .iter()
+                    .map(EvaluatorKind::to_string)
+                    .collect(),
+            },
+            apply: |value, game| {
+                // Scores stored by another evaluator would mislead the search
+                game.transposition_table.lock().unwrap().clear();
+                Game {
+                    evaluator: value.text().parse().unwrap_or(game.evaluator),
+                    ..game
+                }
+            },
+        },
+        UciOption {
+            name: "Hash",
+            option_type: OptionType::Spin {
+                default: DEFAULT_HASH_MB as i64,
+                min: 1,
+                max: MAX_HASH_MB as i64,
+            },
+            apply: |value, game| Game {
+                transposition_table: Arc::new(Mutex::new(TranspositionTable::new(
+                    value.spin() as usize
+                ))),
+                ..game
+            },
+        },
+        UciOption {
+            name: "Clear Hash",
+            option_type: OptionType::Button,
+            apply: |_, game| {
+                game.transposition_table.lock().unwrap().clear();
+                game
+            },
+        },
+        // The search runs on a single thread, so Threads is fixed at 1. The option is there
+        // for GUIs that always send it
+        UciOption {
+            name: "Threads",
+            option_type: OptionType::Spin {
+                default: 1,
+                min: 1,
+                max: 1,
+            },
+            apply: |_, game| game,
+        },
+        UciOption {
+            name: "UCI_Chess960",
+            option_type: OptionType::Check { default: false },
+            apply: |value, game| Game {
+                chess960: value.check(),
+                ..game
+            },
+        },
+        UciOption {
+            name: "UCI_Variant",
+            option_type: OptionType::Combo {
+                default: Variant::default().to_string(),
+                values: Variant::ALL.iter().map(Variant::to_string).collect(),
+            },
+            apply: |value, game| {
+                let variant = value.text().parse().unwrap_or(game.variant);
+                // Positions and search results of the old variant mean nothing in the new one
+                game.transposition_table.lock().unwrap().clear();
+                Game {
+                    variant,
+                    board: VariantBoard::startpos(variant),
+                    previous_boards: HashMap::new(),
+                    ..game
+                }
+            },
+        },
+    ]
+}
+
+/// Finds the option with the name, which is not case sensitive
+pub fn find(name: &str) -> Option<UciOption> {
+    options()
+        .into_iter()
+        .find(|option| option.name.eq_ignore_ascii_case(name))
+}
+
+/// Returns the game with the option set, or the reason the option could not be set
+pub fn apply(name: &str, value: Option<&str>, game: Game) -> Result<Game, String> {
+    let option = find(name).ok_or_else(|| format!("Unknown option [{name}]"))?;
+    let value = option.parse_value(value)?;
+    Ok((option.apply)(value, game))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handle_uci_input::default_game;
+    use crate::transposition_table::{Bound, TableEntry};
+
+    #[test]
+    fn test_advertised_options() {
+        let lines: Vec<String> = options().iter().map(UciOption::to_string).collect();
+        assert_eq!(
+            lines[0],
+            "option name ForcedCapture type check default true"
+        );
+        assert!(lines.contains(&"option name Hash type spin default 16 min 1 max 1024".to_string()));
+        assert!(lines.contains(&"option name Clear Hash type button".to_string()));
+        assert!(lines.contains(
+            &"option name UCI_Variant type combo default chess var chess var antichess var suicide"
+                .to_string()
+        ));
+        let string = UciOption {
+            name: "Book File",
+            option_type: OptionType::String {
+                default: String::new(),
+            },
+            apply: |_, game| game,
+        };
+        assert_eq!(
+            string.to_string(),
+            "option name Book File type string default <empty>"
+        );
+    }
+
+    #[test]
+    fn test_defaults_match_the_default_game() {
+        let game = default_game();
+        for option in options() {
+            let value = match option.option_type {
+                OptionType::Check { default } => Some(default.to_string()),
+                OptionType::Spin { default, .. } => Some(default.to_string()),
+                OptionType::Combo { default, .. } | OptionType::String { default } => Some(default),
+                OptionType::Button => continue,
+            };
+            let set = apply(option.name, value.as_deref(), game.clone()).unwrap();
+            assert_eq!(set.forced_capture, game.forced_capture, "{}", option.name);
+            assert_eq!(set.depth, game.depth, "{}", option.name);
+            assert_eq!(set.skill, game.skill, "{}", option.name);
+            assert_eq!(set.chess960, game.chess960, "{}", option.name);
+            assert_eq!(set.variant, game.variant, "{}", option.name);
+            assert_eq!(set.evaluator, game.evaluator, "{}", option.name);
+        }
+    }
+
+    #[test]
+    fn test_values_are_validated() {
+        let game = default_game();
+        let set = |name, value| apply(name, Some(value), game.clone());
+        assert!(!set("forcedcapture", "FALSE").unwrap().forced_capture);
+        assert_eq!(set("Depth", "5").unwrap().depth, 5);
+        assert_eq!(set("skill level", "5").unwrap().skill, 5);
+        assert!(set("Skill Level", "21").is_err());
+        assert_eq!(
+            set("UCI_Variant", "Antichess").unwrap().variant,
+            Variant::Antichess
+        );
+        assert!(set("ForcedCapture", "maybe").is_err());
+        assert!(set("Depth", "0").is_err());
+        assert!(set("Hash", "lots").is_err());
+        assert!(set("Threads", "2").is_err());
+        assert!(set("UCI_Variant", "crazyhouse").is_err());
+        assert_eq!(
+            set("Evaluator", "Random").unwrap().evaluator,
+            EvaluatorKind::Random
+        );
+        assert!(set("Evaluator", "neural").is_err());
+        assert!(set("Ponder", "true").is_err());
+        assert!(apply("Depth", None, game.clone()).is_err());
+        assert!(apply("Clear Hash", None, game.clone()).is_ok());
+    }
+
+    #[test]
+    fn test_changing_the_rules_clears_the_table() {
+        let game = default_game();
+        for (name, value) in [
+            ("ForcedCapture", "false"),
+            ("Evaluator", "material"),
+            ("UCI_Variant", "antichess"),
+        ] {
+            game.transposition_table.lock().unwrap().store(TableEntry {
+                key: 42,
+                depth: 3,
+                bound: Bound::Exact,
+                evaluation: 10,
+                best_move: None,
+            });
+            let set = apply(name, Some(value), game.clone()).unwrap();
+            assert_eq!(
+                set.transposition_table.lock().unwrap().probe(42),
+                None,
+                "{name}"
+            );
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use crate::antichess::AntichessBoard;
+use crate::draw_rules::{self, DrawReason};
+use crate::predicted_eval::get_truncated_eval;
+use cozy_chess::{BitBoard, Board, Color, GameStatus, Move, Piece, Square};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// The rules the engine plays by, selected with the UCI_Variant option
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Variant {
+    /// Standard chess
+    #[default]
+    Standard,
+    /// Losing chess, where the aim is to lose every piece
+    Antichess,
+    /// Losing chess as played on FICS, where a player who can not move
+    /// only wins if they have fewer pieces left than their opponent
+    Suicide,
+}
+
+impl Variant {
+    /// The variants the engine supports, in the order they are advertised
+    pub const ALL: [Variant; 3] = [Variant::Standard, Variant::Antichess, Variant::Suicide];
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Variant, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "chess" | "standard" => Ok(Variant::Standard),
+            "antichess" => Ok(Variant::Antichess),
+            "suicide" => Ok(Variant::Suicide),
+            _ => Err(format!("Unknown variant [{name}]")),
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    /// Writes the name of the variant as used by UCI_Variant
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Variant::Standard => write!(f, "chess"),
+            Variant::Antichess => write!(f, "antichess"),
+            Variant::Suicide => write!(f, "suicide"),
+        }
+    }
+}
+
+/// How a finished game ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Draw,
+    Winner(Color),
+}
+
+/// A position of any of the supported variants.
+/// The search only uses this type, so it does not need to know which rules are in play
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VariantBoard {
+    Standard(Board),
+    Antichess(AntichessBoard),
+    Suicide(AntichessBoard),
+}
+
+impl VariantBoard {
+    /// Returns the starting position of the variant
+    pub fn startpos(variant: Variant) -> VariantBoard {
+        match variant {
+            Variant::Standard => VariantBoard::Standard(Board::startpos()),
+            Variant::Antichess => VariantBoard::Antichess(AntichessBoard::startpos()),
+            Variant::Suicide => VariantBoard::Suicide(AntichessBoard::startpos()),
+        }
+    }
+
+    /// Reads a position of the variant from a FEN
+    pub fn from_fen(fen: &str, variant: Variant) -> Result<VariantBoard, String> {
+        match variant {
+            Variant::Standard => Board::from_fen(fen, false)
+                .map(VariantBoard::Standard)
+                .map_err(|error| error.to_string()),
+            Variant::Antichess => AntichessBoard::from_fen(fen)
+                .map(VariantBoard::Antichess)
+                .map_err(|error| error.to_string()),
+            Variant::Suicide => AntichessBoard::from_fen(fen)
+                .map(VariantBoard::Suicide)
+                .map_err(|error| error.to_string()),
+        }
+    }
+
+    /// Returns the rules the position is played by
+    pub fn variant(&self) -> Variant {
+        match self {
+            VariantBoard::Standard(_) => Variant::Standard,
+            VariantBoard::Antichess(_) => Variant::Antichess,
+            VariantBoard::Suicide(_) => Variant::Suicide,
+        }
+    }
+
+    pub fn side_to_move(&self) -> Color {
+        match self {
+            VariantBoard::Standard(board) => board.side_to_move(),
+            VariantBoard::Antichess(board) | VariantBoard::Suicide(board) => board.side_to_move(),
+        }
+    }
+
+    pub fn halfmove_clock(&self) -> u8 {
+        match self {
+            VariantBoard::Standard(board) => board.halfmove_clock(),
+            VariantBoard::Antichess(board) | VariantBoard::Suicide(board) => board.halfmove_clock(),
+        }
+    }
+
+    pub fn fullmove_number(&self) -> u16 {
+        match self {
+            VariantBoard::Standard(board) => board.fullmove_number(),
+            VariantBoard::Antichess(board) | VariantBoard::Suicide(board) => {
+                board.fullmove_number()
+            }
+        }
+    }
+
+    pub fn piece_on(&self, square: Square) -> Option<Piece> {
+        match self {
+            VariantBoard::Standard(board) => board.piece_on(square),
+            VariantBoard::Antichess(board) | VariantBoard::Suicide(board) => board.piece_on(square),
+        }
+    }
+
+    pub fn color_on(&self, square: Square) -> Option<Color> {
+        match self {
+            VariantBoard::Standard(board) => board.color_on(square),
+            VariantBoard::Antichess(board) | VariantBoard::Suicide(board) => board.color_on(square),
+        }
+    }
+
+    /// Returns the squares of every piece of this type and colour
+    pub fn colored_pieces(&self, color: Color, piece: Piece) -> BitBoard {
+        match self {
+            VariantBoard::Standard(board) => board.colored_pieces(color, piece),
+            VariantBoard::Antichess(board) | VariantBoard::Suicide(board) => {
+                board.colored_pieces(color, piece)
+            }
+        }
+    }
+
+    /// Returns the squares of every piece of this colour
+    pub fn colors(&self, color: Color) -> BitBoard {
+        match self {
+            VariantBoard::Standard(board) => board.colors(color),
+            VariantBoard::Antichess(board) | VariantBoard::Suicide(board) => board.colors(color),
+        }
+    }
+
+    /// Returns the squares of every piece on the board
+    pub fn occupied(&self) -> BitBoard {
+        match self {
+            VariantBoard::Standard(board) => board.occupied(),
+            VariantBoard::Antichess(board) | VariantBoard::Suicide(board) => board.occupied(),
+        }
+    }
+
+    /// Returns the zobrist hash of the position
+    pub fn hash(&self) -> u64 {
+        match self {
+            VariantBoard::Standard(board) => board.hash(),
+            VariantBoard::Antichess(board) | VariantBoard::Suicide(board) => board.hash(),
+        }
+    }
+
+    /// Returns the key used to count repetitions of the position,
+    /// see draw_rules::repetition_key
+    pub fn repetition_key(&self) -> u64 {
+        match self {
+            VariantBoard::Standard(board) => draw_rules::repetition_key(board),
+            // Antichess boards only keep en passant squares that can be captured on
+            VariantBoard::Antichess(board) | VariantBoard::Suicide(board) => board.hash(),
+        }
+    }
+
+    /// Returns the moves the side to move may play.
+    /// In standard chess captures are only compulsory when forced_capture is set,
+    /// in antichess they always are
+    pub fn legal_moves(&self, forced_capture: bool) -> Vec<Move> {
+        let board = match self {
+            VariantBoard::Standard(board) => board,
+            VariantBoard::Antichess(board) | VariantBoard::Suicide(board) => {
+                return board.generate_moves()
+            }
+        };
+        let enemy_pieces = board.colors(!board.side_to_move());
+        let mut capture_moves: Vec<Move> = Vec::new();
+        let mut all_moves: Vec<Move> = Vec::new();
+        board.generate_moves(|moves| {
+            let mut captures = moves;
+            captures.to &= enemy_pieces;
+            capture_moves.extend(captures);
+            all_moves.extend(moves);
+            false
+        });
+        if forced_capture && !capture_moves.is_empty() {
+            capture_moves
+        } else {
+            all_moves
+        }
+    }
+
+    /// Returns true if the move is legal, ignoring the ForcedCapture option
+    pub fn is_legal(&self, played_move: Move) -> bool {
+        match self {
+            VariantBoard::Standard(board) => board.is_legal(played_move),
+            VariantBoard::Antichess(board) | VariantBoard::Suicide(board) => {
+                board.is_legal(played_move)
+            }
+        }
+    }
+
+    /// Plays a legal move
+    pub fn play(&mut self, played_move: Move) {
+        match self {
+            VariantBoard::Standard(board) => board.play(played_move),
+            VariantBoard::Antichess(board) | VariantBoard::Suicide(board) => {
+                board.play(played_move)
+            }
+        }
+    }
+
+    /// Returns the reason the position is drawn, other than stalemate in standard chess.
+    /// repetitions is the number of times the position has occurred, including now
+    pub fn draw_reason(&self, repetitions: i32) -> Option<DrawReason> {
+        match self {
+            VariantBoard::Standard(board) => draw_rules::draw_reason(board, repetitions),
+            VariantBoard::Antichess(board) | VariantBoard::Suicide(board) => {
+                draw_rules::rule_draw_reason(
+                    repetitions,
+                    board.halfmove_clock(),
+                    board.winner().is_some(),
+                )
+            }
+        }
+    }
+
+    /// Returns how the game ended, or None if it is still going
+    pub fn outcome(&self, repetitions: i32) -> Option<Outcome> {
+        match self {
+            VariantBoard::Standard(board) => match draw_rules::game_status(board, repetitions) {
+                GameStatus::Drawn => Some(Outcome::Draw),
+                // The side to move has been checkmated
+                GameStatus::Won => Some(Outcome::Winner(!board.side_to_move())),
+                GameStatus::Ongoing => None,
+            },
+            VariantBoard::Antichess(board) => match board.winner() {
+                Some(winner) => Some(Outcome::Winner(winner)),
+                None => self.draw_reason(repetitions).map(|_| Outcome::Draw),
+            },
+            VariantBoard::Suicide(board) => {
+                if board.winner().is_none() {
+                    return self.draw_reason(repetitions).map(|_| Outcome::Draw);
+                }
+                // The side to move is out of moves, the side with fewer pieces wins
+                let side_to_move = board.side_to_move();
+                let our_pieces = board.colors(side_to_move).len();
+                let their_pieces = board.colors(!side_to_move).len();
+                Some(match our_pieces.cmp(&their_pieces) {
+                    Ordering::Less => Outcome::Winner(side_to_move),
+                    Ordering::Greater => Outcome::Winner(!side_to_move),
+                    Ordering::Equal => Outcome::Draw,
+                })
+            }
+        }
+    }
+
+    /// Returns the static evaluation of the position for white
+    pub fn evaluate(&self) -> i32 {
+        match self {
+            VariantBoard::Standard(board) => get_truncated_eval(board),
+            VariantBoard::Antichess(board) | VariantBoard::Suicide(board) => board.evaluate(),
+        }
+    }
+}
+
+impl From<Board> for VariantBoard {
+    fn from(board: Board) -> VariantBoard {
+        VariantBoard::Standard(board)
+    }
+}
+
+impl fmt::Display for VariantBoard {
+    /// Writes the position as a FEN, or a Shredder-FEN with the alternate flag
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VariantBoard::Standard(board) => fmt::Display::fmt(board, f),
+            VariantBoard::Antichess(board) | VariantBoard::Suicide(board) => write!(f, "{board}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variant_names() {
+        for variant in Variant::ALL {
+            assert_eq!(variant.to_string().parse::<Variant>(), Ok(variant));
+        }
+        assert_eq!("Antichess".parse::<Variant>(), Ok(Variant::Antichess));
+        assert!("crazyhouse".parse::<Variant>().is_err());
+    }
+
+    #[test]
+    fn test_outcome() {
+        let mated = VariantBoard::from_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1", Variant::Standard);
+        assert_eq!(
+            mated.unwrap().outcome(1),
+            Some(Outcome::Winner(Color::White))
+        );
+        // The same material is a win for the side to move in antichess
+        let stalemated = VariantBoard::from_fen("8/8/8/8/8/p7/P7/8 b - - 0 1", Variant::Antichess);
+        assert_eq!(
+            stalemated.unwrap().outcome(1),
+            Some(Outcome::Winner(Color::Black))
+        );
+        // In suicide the stalemated side needs fewer pieces to win
+        let fewer = VariantBoard::from_fen("8/8/8/8/8/p7/P7/7R b - - 0 1", Variant::Suicide);
+        assert_eq!(
+            fewer.unwrap().outcome(1),
+            Some(Outcome::Winner(Color::Black))
+        );
+        let more = VariantBoard::from_fen("8/8/8/8/p7/p7/P7/8 b - - 0 1", Variant::Suicide);
+        assert_eq!(
+            more.unwrap().outcome(1),
+            Some(Outcome::Winner(Color::White))
+        );
+        let equal = VariantBoard::from_fen("8/8/8/8/8/p7/P7/8 b - - 0 1", Variant::Suicide);
+        assert_eq!(equal.unwrap().outcome(1), Some(Outcome::Draw));
+        let antichess = VariantBoard::startpos(Variant::Antichess);
+        assert_eq!(antichess.outcome(1), None);
+        assert_eq!(antichess.outcome(3), Some(Outcome::Draw));
+    }
+
+    #[test]
+    fn test_forced_capture_only_applies_to_standard_when_set() {
+        let fen = "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2";
+        let standard = VariantBoard::from_fen(fen, Variant::Standard).unwrap();
+        assert_eq!(standard.legal_moves(true), vec!["e4d5".parse().unwrap()]);
+        assert_eq!(standard.legal_moves(false).len(), 31);
+        let antichess = VariantBoard::from_fen(fen, Variant::Antichess).unwrap();
+        assert_eq!(antichess.legal_moves(false), vec!["e4d5".parse().unwrap()]);
+    }
+}
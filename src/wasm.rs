@@ -0,0 +1,137 @@
+use crate::engine_api::Engine;
+use crate::handle_uci_output::{uci_line, uci_move};
+use crate::time_management::parse_go;
+use crate::uci_command::PositionStart;
+use crate::variant::Outcome;
+use cozy_chess::Color;
+use js_sys::Function;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsValue;
+
+/// The engine as seen from JavaScript.
+/// Moves are in UCI notation and evaluations are in centipawns for white
+#[wasm_bindgen(js_name = Wheatley)]
+pub struct WasmEngine {
+    engine: Engine,
+}
+
+/// The result of a search, as seen from JavaScript
+#[wasm_bindgen]
+pub struct SearchResult {
+    best_move: String,
+    pv: String,
+    evaluation: i32,
+}
+
+#[wasm_bindgen]
+impl SearchResult {
+    #[wasm_bindgen(getter)]
+    pub fn best_move(&self) -> String {
+        self.best_move.clone()
+    }
+
+    /// The line the engine expects, separated by spaces
+    #[wasm_bindgen(getter)]
+    pub fn pv(&self) -> String {
+        self.pv.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn evaluation(&self) -> i32 {
+        self.evaluation
+    }
+}
+
+impl Default for WasmEngine {
+    fn default() -> Self {
+        WasmEngine::new()
+    }
+}
+
+#[wasm_bindgen(js_class = Wheatley)]
+impl WasmEngine {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmEngine {
+        WasmEngine {
+            engine: Engine::new(),
+        }
+    }
+
+    /// Starts a new game from the starting position of the current variant
+    pub fn new_game(&mut self) {
+        self.engine.new_game();
+    }
+
+    /// Sets an option by its UCI name, such as UCI_Variant
+    pub fn set_option(&mut self, name: &str, value: Option<String>) -> Result<(), JsValue> {
+        self.engine
+            .set_option(name, value.as_deref())
+            .map_err(|error| JsValue::from_str(&error))
+    }
+
+    pub fn set_fen(&mut self, fen: &str) -> Result<(), JsValue> {
+        let start = PositionStart::Fen(fen.to_string());
+        self.engine
+            .set_position(&start, &[])
+            .map_err(|error| JsValue::from_str(&error))
+    }
+
+    /// The position as a FEN
+    pub fn fen(&self) -> String {
+        self.engine.board().to_string()
+    }
+
+    pub fn play_move(&mut self, uci_move: &str) -> Result<(), JsValue> {
+        self.engine
+            .play_move(uci_move)
+            .map(|_| ())
+            .map_err(|error| JsValue::from_str(&error))
+    }
+
+    /// Searches the position and returns the move the engine wants to play,
+    /// or undefined if the game is over.
+    /// The limits are written as the arguments of the UCI go command, such as
+    /// "movetime 1000" or "depth 4". The search runs on the calling thread, so
+    /// should_stop is called every so often and the search ends once it returns true
+    pub fn best_move(
+        &mut self,
+        limits: &str,
+        should_stop: Option<Function>,
+    ) -> Option<SearchResult> {
+        let tokens: Vec<&str> = std::iter::once("go")
+            .chain(limits.split_whitespace())
+            .collect();
+        let mut interrupt = || match &should_stop {
+            Some(should_stop) => should_stop
+                .call0(&JsValue::NULL)
+                .is_ok_and(|stop| stop.is_truthy()),
+            None => false,
+        };
+        let move_eval = self
+            .engine
+            .search_interruptible(&parse_go(&tokens), &mut interrupt)?;
+        let board = self.engine.board();
+        let chess960 = self.engine.chess960();
+        Some(SearchResult {
+            best_move: uci_move(board, move_eval.best_move, chess960),
+            pv: uci_line(board, &move_eval.pv, chess960),
+            evaluation: move_eval.evaluation,
+        })
+    }
+
+    /// The static evaluation of the position
+    pub fn evaluate(&self) -> i32 {
+        self.engine.evaluate()
+    }
+
+    /// The result of the game as 1-0, 0-1 or 1/2-1/2, or * while it is being played
+    pub fn result(&self) -> String {
+        match self.engine.outcome() {
+            Some(Outcome::Winner(Color::White)) => "1-0",
+            Some(Outcome::Winner(Color::Black)) => "0-1",
+            Some(Outcome::Draw) => "1/2-1/2",
+            None => "*",
+        }
+        .to_string()
+    }
+}
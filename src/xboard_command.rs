@@ -0,0 +1,229 @@
+use std::fmt;
+use std::time::Duration;
+
+/// A command sent by an XBoard or WinBoard GUI
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum XboardCommand {
+    Xboard,
+    /// The version of the protocol the GUI speaks
+    Protover(u32),
+    New,
+    Variant(String),
+    Force,
+    Go,
+    PlayOther,
+    Level {
+        /// The number of moves to play before the clock is refilled, 0 for the whole game
+        moves_per_session: u32,
+        base: Duration,
+        increment: Duration,
+    },
+    /// Think exactly this long on every move
+    St(Duration),
+    /// Search no deeper than this many iterations
+    Sd(i32),
+    /// The time left on the engine's clock
+    Time(Duration),
+    /// The time left on the opponent's clock
+    Otim(Duration),
+    /// A move of the opponent in coordinate notation
+    UserMove(String),
+    /// Play the best move found so far
+    MoveNow,
+    Ping(String),
+    SetBoard(String),
+    /// The game ended, with the result and an optional comment
+    Result(String),
+    Post,
+    NoPost,
+    Quit,
+    /// Commands that need no response from this engine
+    Ignored,
+}
+
+/// The reasons a line sent by the GUI is not a valid command
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum XboardParseError {
+    /// The line holds nothing but whitespace
+    Empty,
+    /// The line does not start with a known command
+    UnknownCommand(String),
+    /// The command is missing an argument or was given one it does not understand
+    InvalidArgument(String),
+}
+
+impl fmt::Display for XboardParseError {
+    /// Writes the error the way the protocol expects errors to be reported
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XboardParseError::Empty => write!(f, "Error (empty command)"),
+            XboardParseError::UnknownCommand(line) => write!(f, "Error (unknown command): {line}"),
+            XboardParseError::InvalidArgument(line) => {
+                write!(f, "Error (invalid argument): {line}")
+            }
+        }
+    }
+}
+
+/// Parses a line sent by the GUI
+pub fn parse(line: &str) -> Result<XboardCommand, XboardParseError> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let Some((command, arguments)) = tokens.split_first() else {
+        return Err(XboardParseError::Empty);
+    };
+    let invalid = || XboardParseError::InvalidArgument(tokens.join(" "));
+    let parsed = match *command {
+        "xboard" => XboardCommand::Xboard,
+        "protover" => XboardCommand::Protover(parse_argument(arguments).ok_or_else(invalid)?),
+        "new" => XboardCommand::New,
+        "variant" => XboardCommand::Variant(arguments.first().ok_or_else(invalid)?.to_string()),
+        "force" => XboardCommand::Force,
+        "go" => XboardCommand::Go,
+        "playother" => XboardCommand::PlayOther,
+        "level" => parse_level(arguments).ok_or_else(invalid)?,
+        "st" => {
+            let seconds: f64 = parse_argument(arguments).ok_or_else(invalid)?;
+            XboardCommand::St(Duration::try_from_secs_f64(seconds).map_err(|_| invalid())?)
+        }
+        "sd" => XboardCommand::Sd(parse_argument(arguments).ok_or_else(invalid)?),
+        "time" => XboardCommand::Time(parse_centiseconds(arguments).ok_or_else(invalid)?),
+        "otim" => XboardCommand::Otim(parse_centiseconds(arguments).ok_or_else(invalid)?),
+        "usermove" => XboardCommand::UserMove(arguments.first().ok_or_else(invalid)?.to_string()),
+        "?" => XboardCommand::MoveNow,
+        "ping" => XboardCommand::Ping(arguments.join(" ")),
+        "setboard" if !arguments.is_empty() => XboardCommand::SetBoard(arguments.join(" ")),
+        "setboard" => return Err(invalid()),
+        "result" => XboardCommand::Result(arguments.join(" ")),
+        "post" => XboardCommand::Post,
+        "nopost" => XboardCommand::NoPost,
+        "quit" => XboardCommand::Quit,
+        "accepted" | "rejected" | "random" | "hard" | "easy" | "computer" | "name" | "rating"
+        | "ics" | "draw" => XboardCommand::Ignored,
+        // Moves are sent without usermove if the GUI did not accept that feature
+        text if tokens.len() == 1 && is_coordinate_move(text) => {
+            XboardCommand::UserMove(text.to_string())
+        }
+        _ => return Err(XboardParseError::UnknownCommand(tokens.join(" "))),
+    };
+    Ok(parsed)
+}
+
+fn parse_argument<T: std::str::FromStr>(arguments: &[&str]) -> Option<T> {
+    arguments.first()?.parse().ok()
+}
+
+/// Clock times are sent in hundredths of a second, and may be negative once a flag has fallen
+fn parse_centiseconds(arguments: &[&str]) -> Option<Duration> {
+    let centiseconds: i64 = parse_argument(arguments)?;
+    Some(Duration::from_millis(centiseconds.max(0) as u64 * 10))
+}
+
+/// Parses level MPS BASE INC, where BASE is in minutes or minutes:seconds
+/// and INC is in seconds
+fn parse_level(arguments: &[&str]) -> Option<XboardCommand> {
+    let [moves_per_session, base, increment] = arguments else {
+        return None;
+    };
+    let base = match base.split_once(':') {
+        Some((minutes, seconds)) => {
+            let minutes: u64 = minutes.parse().ok()?;
+            let seconds: u64 = seconds.parse().ok()?;
+            Duration::from_secs(minutes * 60 + seconds)
+        }
+        None => Duration::from_secs(base.parse::<u64>().ok()? * 60),
+    };
+    Some(XboardCommand::Level {
+        moves_per_session: moves_per_session.parse().ok()?,
+        base,
+        increment: Duration::try_from_secs_f64(increment.parse().ok()?).ok()?,
+    })
+}
+
+/// Returns true if the text looks like a move such as e2e4 or e7e8q
+fn is_coordinate_move(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    let is_square =
+        |file: u8, rank: u8| (b'a'..=b'h').contains(&file) && (b'1'..=b'8').contains(&rank);
+    match bytes {
+        [from_file, from_rank, to_file, to_rank, rest @ ..] => {
+            is_square(*from_file, *from_rank)
+                && is_square(*to_file, *to_rank)
+                && (rest.is_empty() || matches!(rest, [b'q' | b'r' | b'b' | b'n' | b'k']))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simple_commands() {
+        assert_eq!(parse("xboard\n"), Ok(XboardCommand::Xboard));
+        assert_eq!(parse("protover 2"), Ok(XboardCommand::Protover(2)));
+        assert_eq!(parse("?"), Ok(XboardCommand::MoveNow));
+        assert_eq!(parse("ping 7"), Ok(XboardCommand::Ping("7".to_string())));
+        assert_eq!(parse("accepted usermove"), Ok(XboardCommand::Ignored));
+        assert_eq!(parse(" "), Err(XboardParseError::Empty));
+        assert!(matches!(
+            parse("fly"),
+            Err(XboardParseError::UnknownCommand(_))
+        ));
+    }
+
+    #[test]
+    fn test_moves() {
+        let user_move = Ok(XboardCommand::UserMove("e7e8q".to_string()));
+        assert_eq!(parse("usermove e7e8q"), user_move);
+        assert_eq!(parse("e7e8q"), user_move);
+        assert!(parse("e9e8").is_err());
+        assert!(parse("usermove").is_err());
+    }
+
+    #[test]
+    fn test_time_controls() {
+        assert_eq!(
+            parse("level 40 5 0"),
+            Ok(XboardCommand::Level {
+                moves_per_session: 40,
+                base: Duration::from_secs(300),
+                increment: Duration::ZERO,
+            })
+        );
+        assert_eq!(
+            parse("level 0 2:30 1.5"),
+            Ok(XboardCommand::Level {
+                moves_per_session: 0,
+                base: Duration::from_secs(150),
+                increment: Duration::from_millis(1500),
+            })
+        );
+        assert!(parse("level 40 5").is_err());
+        assert_eq!(
+            parse("st 10"),
+            Ok(XboardCommand::St(Duration::from_secs(10)))
+        );
+        assert_eq!(parse("sd 4"), Ok(XboardCommand::Sd(4)));
+        assert_eq!(
+            parse("time 12345"),
+            Ok(XboardCommand::Time(Duration::from_millis(123450)))
+        );
+        assert_eq!(parse("otim -50"), Ok(XboardCommand::Otim(Duration::ZERO)));
+    }
+
+    #[test]
+    fn test_setboard_and_result() {
+        assert_eq!(
+            parse("setboard 4k3/8/8/8/8/8/8/4K3 w - - 0 1"),
+            Ok(XboardCommand::SetBoard(
+                "4k3/8/8/8/8/8/8/4K3 w - - 0 1".to_string()
+            ))
+        );
+        assert!(parse("setboard").is_err());
+        assert_eq!(
+            parse("result 1-0 {White mates}"),
+            Ok(XboardCommand::Result("1-0 {White mates}".to_string()))
+        );
+    }
+}